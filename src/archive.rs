@@ -0,0 +1,72 @@
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+
+use crate::cartridge::RomError;
+
+const ZIP_MAGIC:  [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+const ROM_EXTENSIONS: [&str; 2] = ["gb", "gbc"];
+
+// Raw ROM bytes plus the archive member they came from, if any
+pub struct Unpacked {
+    pub bytes: Vec<u8>,
+    pub entry: Option<String>,
+}
+
+// Sniff the container format from its magic bytes rather than trusting the file extension
+pub fn unpack(path: &Path, data: Vec<u8>, entry: Option<&str>) -> Result<Unpacked, RomError> {
+    if data.starts_with(&ZIP_MAGIC) {
+        unzip(data, entry)
+    } else if data.starts_with(&GZIP_MAGIC) {
+        gunzip(path, data)
+    } else {
+        Ok(Unpacked { bytes: data, entry: None })
+    }
+}
+
+fn unzip(data: Vec<u8>, entry: Option<&str>) -> Result<Unpacked, RomError> {
+    let mut archive = ZipArchive::new(Cursor::new(data)).map_err(|e| RomError::Archive(e.to_string()))?;
+
+    let name = match entry {
+        Some(name) => archive.file_names()
+            .find(|n| *n == name)
+            .map(|n| n.to_string())
+            .ok_or(RomError::EntryNotFound(name.to_string()))?,
+
+        // Archive order, not alphabetical, so a zip with a ROM and its patch picks what the packer put first
+        None => (0..archive.len())
+            .filter_map(|i| archive.by_index(i).ok().map(|f| f.name().to_string()))
+            .find(|n| is_rom_name(n))
+            .ok_or(RomError::NoRomInArchive)?,
+    };
+
+    let mut file  = archive.by_name(&name).map_err(|e| RomError::Archive(e.to_string()))?;
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes)?;
+
+    Ok(Unpacked { bytes: bytes, entry: Some(name) })
+}
+
+fn gunzip(path: &Path, data: Vec<u8>) -> Result<Unpacked, RomError> {
+    let mut decoder = GzDecoder::new(&data[..]);
+    let mut bytes   = Vec::new();
+    decoder.read_to_end(&mut bytes).map_err(|e| RomError::Archive(e.to_string()))?;
+
+    // Prefer the name stored in the gzip header, fall back to the file name minus ".gz"
+    let entry = decoder.header()
+        .and_then(|h| h.filename())
+        .map(|n| String::from_utf8_lossy(n).into_owned())
+        .or_else(|| path.file_stem().map(|n| n.to_string_lossy().into_owned()));
+
+    Ok(Unpacked { bytes: bytes, entry: entry })
+}
+
+fn is_rom_name(name: &str) -> bool {
+    Path::new(name).extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+        .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.as_str()))
+}
@@ -0,0 +1,53 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cartridge::RomError;
+
+const DMG_BOOT_SIZE: usize = 0x100;
+const CGB_BOOT_SIZE: usize = 0x900;
+
+// The CGB boot ROM is split around the cartridge header at 0x0100-0x01FF
+const CGB_HIGH_START: u16 = 0x0200;
+const CGB_HIGH_END:   u16 = 0x08FF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootRomKind { Dmg, Cgb }
+
+// DMG/MGB/SGB boot ROMs are 256 bytes, CGB ones 2304. Which model the image is for only matters
+// for its size, the code itself takes care of the rest.
+pub struct BootRom {
+    bytes:    Vec<u8>,
+    pub kind: BootRomKind,
+}
+
+impl BootRom {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, RomError> {
+        let kind = match bytes.len() {
+            DMG_BOOT_SIZE => BootRomKind::Dmg,
+            CGB_BOOT_SIZE => BootRomKind::Cgb,
+            len => return Err(RomError::InvalidBootRom { len }),
+        };
+
+        Ok(BootRom { bytes: bytes, kind: kind })
+    }
+
+    pub fn from_path(path: &Path) -> Result<Self, RomError> {
+        let bytes = fs::read(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => RomError::NotFound(path.to_path_buf()),
+            _ => RomError::Io(e),
+        })?;
+
+        BootRom::from_bytes(bytes)
+    }
+
+    // Whether the overlay covers this address; everything else falls through to the cartridge
+    pub fn covers(&self, addr: u16) -> bool {
+        match self.kind {
+            BootRomKind::Dmg => (addr as usize) < DMG_BOOT_SIZE,
+            BootRomKind::Cgb => (addr as usize) < DMG_BOOT_SIZE || (CGB_HIGH_START..=CGB_HIGH_END).contains(&addr),
+        }
+    }
+
+    pub fn read_byte(&self, addr: u16) -> u8 { self.bytes[addr as usize] }
+}
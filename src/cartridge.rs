@@ -0,0 +1,583 @@
+use std::fmt;
+use std::{fs, io, path::{Path, PathBuf}, time::Duration};
+
+use crate::archive;
+use crate::patch::{self, PatchError};
+use crate::mbc::{self, Mapper, Infrared, mbc7::Accelerometer, rtc::Rtc};
+use crate::save::SaveFile;
+
+// Header layout (0x0100 - 0x014F)
+const LOGO_START:       usize = 0x0104;
+const TITLE_START:      usize = 0x0134;
+const MANUFACTURER:     usize = 0x013F;
+const CGB_FLAG:         usize = 0x0143;
+const NEW_LICENSEE:     usize = 0x0144;
+const SGB_FLAG:         usize = 0x0146;
+const CARTRIDGE_TYPE:   usize = 0x0147;
+const ROM_SIZE:         usize = 0x0148;
+const RAM_SIZE:         usize = 0x0149;
+const DESTINATION:      usize = 0x014A;
+const OLD_LICENSEE:     usize = 0x014B;
+const VERSION:          usize = 0x014C;
+const HEADER_CHECKSUM:  usize = 0x014D;
+const GLOBAL_CHECKSUM:  usize = 0x014E;
+const HEADER_END:       usize = 0x014F;
+
+const ROM_BANK_SIZE:    usize = 0x4000;
+
+// Nintendo logo as checked by the boot ROM
+const NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+    ];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeKind {
+    RomOnly,            RomRam,             RomRamBattery,
+    Mbc1,               Mbc1Ram,            Mbc1RamBattery,
+    Mbc2,               Mbc2Battery,
+    Mmm01,              Mmm01Ram,           Mmm01RamBattery,
+    Mbc3TimerBattery,   Mbc3TimerRamBattery,
+    Mbc3,               Mbc3Ram,            Mbc3RamBattery,
+    Mbc5,               Mbc5Ram,            Mbc5RamBattery,
+    Mbc5Rumble,         Mbc5RumbleRam,      Mbc5RumbleRamBattery,
+    Mbc6,               Mbc7SensorRumbleRamBattery,
+    PocketCamera,       BandaiTama5,        HuC3,               HuC1RamBattery,
+}
+
+impl CartridgeKind {
+    pub fn from_byte(byte: u8) -> Option<CartridgeKind> {
+        use CartridgeKind::*;
+
+        Some(match byte {
+            0x00 => RomOnly,            0x01 => Mbc1,               0x02 => Mbc1Ram,            0x03 => Mbc1RamBattery,
+            0x05 => Mbc2,               0x06 => Mbc2Battery,        0x08 => RomRam,             0x09 => RomRamBattery,
+            0x0B => Mmm01,              0x0C => Mmm01Ram,           0x0D => Mmm01RamBattery,
+            0x0F => Mbc3TimerBattery,   0x10 => Mbc3TimerRamBattery,
+            0x11 => Mbc3,               0x12 => Mbc3Ram,            0x13 => Mbc3RamBattery,
+            0x19 => Mbc5,               0x1A => Mbc5Ram,            0x1B => Mbc5RamBattery,
+            0x1C => Mbc5Rumble,         0x1D => Mbc5RumbleRam,      0x1E => Mbc5RumbleRamBattery,
+            0x20 => Mbc6,               0x22 => Mbc7SensorRumbleRamBattery,
+            0xFC => PocketCamera,       0xFD => BandaiTama5,        0xFE => HuC3,               0xFF => HuC1RamBattery,
+
+            _    => return None,
+        })
+    }
+
+    pub fn has_ram(&self) -> bool {
+        use CartridgeKind::*;
+
+        matches!(self,
+            RomRam | RomRamBattery | Mbc1Ram | Mbc1RamBattery | Mbc2 | Mbc2Battery |
+            Mmm01Ram | Mmm01RamBattery | Mbc3TimerRamBattery | Mbc3Ram | Mbc3RamBattery |
+            Mbc5Ram | Mbc5RamBattery | Mbc5RumbleRam | Mbc5RumbleRamBattery |
+            Mbc6 | Mbc7SensorRumbleRamBattery | PocketCamera | HuC3 | HuC1RamBattery)
+    }
+
+    pub fn has_battery(&self) -> bool {
+        use CartridgeKind::*;
+
+        matches!(self,
+            RomRamBattery | Mbc1RamBattery | Mbc2Battery | Mmm01RamBattery |
+            Mbc3TimerBattery | Mbc3TimerRamBattery | Mbc3RamBattery |
+            Mbc5RamBattery | Mbc5RumbleRamBattery | Mbc7SensorRumbleRamBattery |
+            HuC3 | HuC1RamBattery)
+    }
+
+    pub fn has_timer(&self) -> bool {
+        use CartridgeKind::*;
+
+        matches!(self, Mbc3TimerBattery | Mbc3TimerRamBattery | HuC3)
+    }
+
+    // Only MBC3 clocks have a save format everyone agrees on
+    pub fn has_rtc_trailer(&self) -> bool {
+        use CartridgeKind::*;
+
+        matches!(self, Mbc3TimerBattery | Mbc3TimerRamBattery)
+    }
+
+    pub fn has_rumble(&self) -> bool {
+        use CartridgeKind::*;
+
+        matches!(self, Mbc5Rumble | Mbc5RumbleRam | Mbc5RumbleRamBattery | Mbc7SensorRumbleRamBattery)
+    }
+}
+
+// Cartridge hardware changes the host may want to react to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeEvent { Rumble(bool), Infrared(bool), SaveFailed }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport { None, Enhanced, Only }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CartridgeError {
+    Truncated       { len: usize },
+    SizeMismatch    { declared: usize, actual: usize },
+    HeaderChecksum  { expected: u8,  actual: u8 },
+    GlobalChecksum  { expected: u16, actual: u16 },
+    UnknownKind(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use CartridgeError::*;
+
+        match self {
+            Truncated { len }                   => write!(f, "ROM is truncated ({} bytes, header needs {})", len, HEADER_END + 1),
+            SizeMismatch { declared, actual }   => write!(f, "header declares {} bytes of ROM but file has {}", declared, actual),
+            HeaderChecksum { expected, actual } => write!(f, "header checksum mismatch (expected {:#04X}, computed {:#04X})", expected, actual),
+            GlobalChecksum { expected, actual } => write!(f, "global checksum mismatch (expected {:#06X}, computed {:#06X})", expected, actual),
+            UnknownKind(byte)                   => write!(f, "unknown cartridge type {:#04X}", byte),
+            UnknownRomSize(byte)                => write!(f, "unknown ROM size code {:#04X}", byte),
+            UnknownRamSize(byte)                => write!(f, "unknown RAM size code {:#04X}", byte),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+// Everything that can go wrong turning a file into a running cartridge
+#[derive(Debug)]
+pub enum RomError {
+    NotFound(PathBuf),
+    Io(io::Error),
+    TooSmall { len: usize },
+    BadHeader(CartridgeError),
+    UnsupportedMapper(u8),
+    Archive(String),
+    NoRomInArchive,
+    EntryNotFound(String),
+    Patch(PathBuf, PatchError),
+    InvalidBootRom { len: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use RomError::*;
+
+        match self {
+            NotFound(path)          => write!(f, "ROM not found: {}", path.display()),
+            Io(e)                   => write!(f, "unable to read ROM: {}", e),
+            TooSmall { len }        => write!(f, "ROM is too small to hold a cartridge header ({} bytes)", len),
+            BadHeader(e)            => write!(f, "invalid cartridge header: {}", e),
+            UnsupportedMapper(byte) => write!(f, "unsupported cartridge type {:#04X}", byte),
+            Archive(e)              => write!(f, "unable to unpack archive: {}", e),
+            NoRomInArchive          => write!(f, "archive doesn't contain a .gb or .gbc file"),
+            EntryNotFound(name)     => write!(f, "archive has no entry named {}", name),
+            Patch(path, e)          => write!(f, "unable to apply {}: {}", path.display(), e),
+            InvalidBootRom { len }  => write!(f, "boot ROM must be 256 (DMG) or 2304 (CGB) bytes, got {}", len),
+        }
+    }
+}
+
+impl std::error::Error for RomError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RomError::Io(e)        => Some(e),
+            RomError::BadHeader(e) => Some(e),
+            RomError::Patch(_, e)  => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for RomError {
+    fn from(e: io::Error) -> Self { RomError::Io(e) }
+}
+
+impl From<CartridgeError> for RomError {
+    fn from(e: CartridgeError) -> Self {
+        match e {
+            CartridgeError::Truncated { len } => RomError::TooSmall { len },
+            CartridgeError::UnknownKind(byte) => RomError::UnsupportedMapper(byte),
+            e => RomError::BadHeader(e),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+    pub title:              String,
+    pub manufacturer:       Option<String>,
+    pub cgb:                CgbSupport,
+    pub sgb:                bool,
+    pub kind:               CartridgeKind,
+    pub rom_size:           usize,
+    pub ram_size:           usize,
+    pub japanese:           bool,
+    pub licensee:           String,
+    pub version:            u8,
+    pub logo_valid:         bool,
+    pub header_checksum:    u8,
+    pub global_checksum:    u16,
+    pub title_checksum:     u8,     // What the CGB boot ROM keys DMG colorization on
+    pub title_fourth:       u8,     // ...along with the 4th title letter when checksums collide
+}
+
+impl CartridgeHeader {
+    pub fn parse(rom: &[u8]) -> Result<Self, CartridgeError> {
+        if rom.len() <= HEADER_END { return Err(CartridgeError::Truncated { len: rom.len() }); }
+
+        let computed = Self::compute_header_checksum(rom);
+        if computed != rom[HEADER_CHECKSUM] {
+            return Err(CartridgeError::HeaderChecksum { expected: rom[HEADER_CHECKSUM], actual: computed });
+        }
+
+        let kind     = CartridgeKind::from_byte(rom[CARTRIDGE_TYPE]).ok_or(CartridgeError::UnknownKind(rom[CARTRIDGE_TYPE]))?;
+        let rom_size = Self::decode_rom_size(rom[ROM_SIZE]).ok_or(CartridgeError::UnknownRomSize(rom[ROM_SIZE]))?;
+        let ram_size = Self::decode_ram_size(rom[RAM_SIZE]).ok_or(CartridgeError::UnknownRamSize(rom[RAM_SIZE]))?;
+
+        let cgb = match rom[CGB_FLAG] {
+            0x80 => CgbSupport::Enhanced,
+            0xC0 => CgbSupport::Only,
+            _    => CgbSupport::None,
+        };
+
+        // Newer carts shrink the title to make room for the manufacturer code and CGB flag
+        let manufacturer = if cgb != CgbSupport::None && rom[MANUFACTURER..CGB_FLAG].iter().all(|b| b.is_ascii_uppercase()) {
+            Some(Self::ascii(&rom[MANUFACTURER..CGB_FLAG]))
+        } else { None };
+        let title_end = if manufacturer.is_some()  { MANUFACTURER }
+                   else if cgb != CgbSupport::None { CGB_FLAG }
+                   else                            { CGB_FLAG + 1 };
+
+        let licensee = if rom[OLD_LICENSEE] == 0x33 {
+            Self::ascii(&rom[NEW_LICENSEE..SGB_FLAG])
+        } else {
+            format!("{:02X}", rom[OLD_LICENSEE])
+        };
+
+        Ok(CartridgeHeader {
+            title:              Self::ascii(&rom[TITLE_START..title_end]),
+            manufacturer,
+            cgb,
+            // The SGB flag is ignored by the SGB BIOS unless the old licensee code is 0x33
+            sgb:                rom[SGB_FLAG] == 0x03 && rom[OLD_LICENSEE] == 0x33,
+            kind,
+            rom_size,
+            ram_size,
+            japanese:           rom[DESTINATION] == 0x00,
+            licensee,
+            version:            rom[VERSION],
+            logo_valid:         rom[LOGO_START..TITLE_START] == NINTENDO_LOGO,
+            header_checksum:    rom[HEADER_CHECKSUM],
+            global_checksum:    (rom[GLOBAL_CHECKSUM] as u16) << 8 | rom[GLOBAL_CHECKSUM + 1] as u16,
+            title_checksum:     rom[TITLE_START..=CGB_FLAG].iter().fold(0u8, |x, b| x.wrapping_add(*b)),
+            title_fourth:       rom[TITLE_START + 3],
+        })
+    }
+
+    // The boot ROM doesn't check this one, so plenty of hacks get it wrong; callers decide if it matters
+    pub fn verify_global_checksum(&self, rom: &[u8]) -> Result<(), CartridgeError> {
+        let computed = Self::compute_global_checksum(rom);
+
+        if computed == self.global_checksum { Ok(()) }
+        else { Err(CartridgeError::GlobalChecksum { expected: self.global_checksum, actual: computed }) }
+    }
+
+    pub fn rom_banks(&self) -> usize { self.rom_size / ROM_BANK_SIZE }
+
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[TITLE_START..HEADER_CHECKSUM].iter().fold(0u8, |x, b| x.wrapping_sub(*b).wrapping_sub(1))
+    }
+
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter().enumerate()
+            .filter(|(i, _)| *i != GLOBAL_CHECKSUM && *i != GLOBAL_CHECKSUM + 1)
+            .fold(0u16, |sum, (_, b)| sum.wrapping_add(*b as u16))
+    }
+
+    fn decode_rom_size(code: u8) -> Option<usize> {
+        match code {
+            0x00..=0x08 => Some((32 * 1024) << code),
+            0x52        => Some(72 * ROM_BANK_SIZE),
+            0x53        => Some(80 * ROM_BANK_SIZE),
+            0x54        => Some(96 * ROM_BANK_SIZE),
+            _           => None,
+        }
+    }
+
+    fn decode_ram_size(code: u8) -> Option<usize> {
+        match code {
+            0x00 => Some(0),
+            0x01 => Some(2 * 1024),     // Unofficial, used by a handful of homebrew carts
+            0x02 => Some(8 * 1024),
+            0x03 => Some(32 * 1024),
+            0x04 => Some(128 * 1024),
+            0x05 => Some(64 * 1024),
+            _    => None,
+        }
+    }
+
+    fn ascii(bytes: &[u8]) -> String {
+        bytes.iter()
+            .take_while(|b| **b != 0x00)
+            .map(|b| if b.is_ascii_graphic() || *b == b' ' { *b as char } else { '?' })
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+}
+
+pub(crate) struct ROM {
+    bytes:  Vec<u8>,
+    ram:    Vec<u8>,
+    mapper: Box<dyn Mapper>,
+    save:   Option<SaveFile>,
+    pub header: CartridgeHeader,
+    pub entry:  Option<String>,     // Archive member the ROM was unpacked from
+    pub patch:  Option<PathBuf>,    // Soft patch applied on load
+    save_failed: bool,              // Reported through poll_event so the host can warn the player
+}
+
+impl ROM {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, RomError> {
+        ROM::open(path.as_ref(), None, None)
+    }
+
+    pub fn from_archive_entry<P: AsRef<Path>>(path: P, entry: Option<&str>) -> Result<Self, RomError> {
+        ROM::open(path.as_ref(), entry, None)
+    }
+
+    // Plain ROMs load as-is; zip archives use the named entry or the first .gb/.gbc inside,
+    // gzip streams are decompressed. Without an explicit patch, a .ips/.ups/.bps with the ROM's
+    // name gets applied if there is one.
+    pub fn open(path: &Path, entry: Option<&str>, patch: Option<&Path>) -> Result<Self, RomError> {
+        let data = ROM::read_file(path)?;
+        let mut unpacked = archive::unpack(path, data, entry)?;
+
+        let patch = patch.map(Path::to_path_buf).or_else(|| patch::find_beside(path));
+        if let Some(patch_path) = patch.as_ref() {
+            let patch_data = ROM::read_file(patch_path)?;
+            unpacked.bytes = patch::apply(&unpacked.bytes, &patch_data)
+                .map_err(|e| RomError::Patch(patch_path.clone(), e))?;
+        }
+
+        let mut rom = ROM::load(unpacked.bytes, Some(path))?;
+        rom.entry   = unpacked.entry;
+        rom.patch   = patch;
+
+        Ok(rom)
+    }
+
+    fn read_file(path: &Path) -> Result<Vec<u8>, RomError> {
+        fs::read(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => RomError::NotFound(path.to_path_buf()),
+            _ => RomError::Io(e),
+        })
+    }
+
+    // ROMs that don't come from a file don't get a .sav either
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, RomError> {
+        ROM::load(bytes, None)
+    }
+
+    // Overdumps and trimmed homebrew are common; short images get padded with open bus 0xFF
+    fn load(mut bytes: Vec<u8>, path: Option<&Path>) -> Result<Self, RomError> {
+        let header = CartridgeHeader::parse(&bytes)?;
+        if bytes.len() != header.rom_size {
+            eprintln!("Warning: {}", CartridgeError::SizeMismatch { declared: header.rom_size, actual: bytes.len() });
+            if bytes.len() < header.rom_size { bytes.resize(header.rom_size, 0xFF); }
+        }
+
+        let mapper = mbc::create(&header).ok_or(RomError::UnsupportedMapper(bytes[CARTRIDGE_TYPE]))?;
+        let save   = match path {
+            Some(path) if header.kind.has_battery() => Some(SaveFile::for_rom(path)),
+            _ => None,
+        };
+
+        let mut rom = ROM { bytes: bytes, ram: vec![0; mbc::ram_size(&header)], mapper: mapper, save: save, header: header, entry: None, patch: None, save_failed: false };
+        rom.load_save()?;
+
+        Ok(rom)
+    }
+
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x7FFF => { self.mapper.read_rom(&self.bytes, addr) }
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_byte(&mut self, addr: u16, val: u8) {
+        let ram_was_enabled = self.mapper.ram_enabled();
+
+        if let 0x0000..=0x7FFF = addr { self.mapper.write_rom(addr, val) }
+
+        // Games disable RAM once they're done saving, which makes a good moment to hit the disk
+        if ram_was_enabled && !self.mapper.ram_enabled() && self.save.as_ref().is_some_and(|s| s.dirty) {
+            self.autosave();
+        }
+    }
+
+    pub fn read_ram(&self, addr: u16) -> u8 { self.mapper.read_ram(&self.ram, addr) }
+
+    // Raw external RAM across all banks, bypassing the mapper
+    pub fn ram(&self) -> &[u8] { &self.ram }
+
+    pub fn write_ram(&mut self, addr: u16, val: u8) {
+        self.mapper.write_ram(&mut self.ram, addr, val);
+        if let Some(save) = self.save.as_mut() { save.dirty = true; }
+    }
+
+    pub fn step(&mut self, cycles: u16) {
+        self.mapper.step(cycles);
+
+        if self.save.as_ref().is_some_and(|s| s.due()) { self.autosave(); }
+    }
+
+    pub fn rtc(&mut self) -> Option<&mut Rtc> { self.mapper.rtc() }
+
+    pub fn infrared(&mut self) -> Option<&mut Infrared> { self.mapper.infrared() }
+
+    pub fn accelerometer(&mut self) -> Option<&mut Accelerometer> { self.mapper.accelerometer() }
+
+    pub fn poll_event(&mut self) -> Option<CartridgeEvent> {
+        if std::mem::take(&mut self.save_failed) { return Some(CartridgeEvent::SaveFailed); }
+        self.mapper.poll_event()
+    }
+
+    pub fn set_save_interval(&mut self, interval: Duration) {
+        if let Some(save) = self.save.as_mut() { save.set_interval(interval); }
+    }
+
+    // Write battery RAM (and the MBC3 clock) out to the .sav file
+    pub fn flush(&mut self) -> io::Result<()> {
+        let Some(save) = self.save.as_mut() else { return Ok(()); };
+        let rtc = if self.header.kind.has_rtc_trailer() { self.mapper.rtc() } else { None };
+
+        save.store(&self.ram, rtc)
+    }
+
+    fn load_save(&mut self) -> io::Result<()> {
+        let Some(save) = self.save.as_ref() else { return Ok(()); };
+        let rtc = if self.header.kind.has_rtc_trailer() { self.mapper.rtc() } else { None };
+
+        save.load(&mut self.ram, rtc)
+    }
+
+    fn autosave(&mut self) {
+        if let (Err(e), Some(save)) = (self.flush(), self.save.as_ref()) {
+            eprintln!("Unable to write save file {}: {}", save.path().display(), e);
+            self.save_failed = true;
+        }
+    }
+}
+
+impl Drop for ROM {
+    fn drop(&mut self) { self.autosave(); }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // A 32KB ROM-only image with a valid header; tweak bytes then call fix_checksum
+    pub(crate) fn rom_image(kind: u8, rom_code: u8, ram_code: u8) -> Vec<u8> {
+        let mut rom = vec![0; (32 * 1024) << rom_code];
+        rom[LOGO_START..TITLE_START].copy_from_slice(&NINTENDO_LOGO);
+        rom[TITLE_START..TITLE_START + 4].copy_from_slice(b"TEST");
+        rom[CARTRIDGE_TYPE] = kind;
+        rom[ROM_SIZE]       = rom_code;
+        rom[RAM_SIZE]       = ram_code;
+        rom[OLD_LICENSEE]   = 0x01;
+        fix_checksum(&mut rom);
+        rom
+    }
+
+    pub(crate) fn fix_checksum(rom: &mut [u8]) {
+        rom[HEADER_CHECKSUM] = CartridgeHeader::compute_header_checksum(rom);
+    }
+
+    #[test]
+    fn parses_header_fields() {
+        let header = CartridgeHeader::parse(&rom_image(0x13, 0x02, 0x03)).unwrap();
+
+        assert_eq!(header.title, "TEST");
+        assert_eq!(header.kind, CartridgeKind::Mbc3RamBattery);
+        assert_eq!(header.rom_size, 128 * 1024);
+        assert_eq!(header.ram_size, 32 * 1024);
+        assert_eq!(header.licensee, "01");
+        assert_eq!(header.cgb, CgbSupport::None);
+        assert!(header.logo_valid);
+        assert!(!header.sgb);
+    }
+
+    #[test]
+    fn rejects_bad_header_checksum() {
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[HEADER_CHECKSUM] ^= 0xFF;
+
+        assert!(matches!(CartridgeHeader::parse(&rom), Err(CartridgeError::HeaderChecksum { .. })));
+    }
+
+    #[test]
+    fn rejects_unknown_type_and_sizes() {
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+
+        rom[CARTRIDGE_TYPE] = 0x42;
+        fix_checksum(&mut rom);
+        assert_eq!(CartridgeHeader::parse(&rom).unwrap_err(), CartridgeError::UnknownKind(0x42));
+
+        rom[CARTRIDGE_TYPE] = 0x00;
+        rom[ROM_SIZE] = 0x09;
+        fix_checksum(&mut rom);
+        assert_eq!(CartridgeHeader::parse(&rom).unwrap_err(), CartridgeError::UnknownRomSize(0x09));
+
+        rom[ROM_SIZE] = 0x00;
+        rom[RAM_SIZE] = 0x06;
+        fix_checksum(&mut rom);
+        assert_eq!(CartridgeHeader::parse(&rom).unwrap_err(), CartridgeError::UnknownRamSize(0x06));
+    }
+
+    #[test]
+    fn decodes_odd_rom_sizes() {
+        assert_eq!(CartridgeHeader::decode_rom_size(0x05), Some(1024 * 1024));
+        assert_eq!(CartridgeHeader::decode_rom_size(0x52), Some(72 * ROM_BANK_SIZE));
+        assert_eq!(CartridgeHeader::decode_rom_size(0x54), Some(96 * ROM_BANK_SIZE));
+    }
+
+    #[test]
+    fn rejects_truncated_rom() {
+        assert!(matches!(ROM::from_bytes(vec![0; 0x100]), Err(RomError::TooSmall { len: 0x100 })));
+    }
+
+    #[test]
+    fn accepts_overdumps_and_pads_short_images() {
+        let mut rom = rom_image(0x01, 0x01, 0x00);
+        rom.truncate(0x6000);
+        let short = ROM::from_bytes(rom).unwrap();
+        assert_eq!(short.read_byte(0x7FFF), 0xFF);
+
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom.resize(64 * 1024, 0xAA);
+        assert!(ROM::from_bytes(rom).is_ok());
+    }
+
+    #[test]
+    fn flushes_saves_when_ram_gets_disabled() {
+        let dir = std::env::temp_dir().join(format!("autosave-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("game.gb");
+        let save = path.with_extension("sav");
+
+        // MBC2: with address bit 8 set, 0x0000-0x3FFF selects the ROM bank instead
+        let mut rom = ROM::load(rom_image(0x06, 0x01, 0x00), Some(&path)).unwrap();
+        rom.write_byte(0x0000, 0x0A);
+        rom.write_ram(0xA000, 0x05);
+        rom.write_byte(0x0100, 0x02);
+        rom.write_byte(0x2100, 0x03);
+        assert!(!save.exists());
+
+        rom.write_byte(0x0000, 0x00);
+        assert_eq!(fs::read(&save).unwrap()[0], 0x05);
+
+        drop(rom);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
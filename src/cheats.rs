@@ -0,0 +1,232 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    // Patches ROM reads. With a compare byte the patch only applies while the original value
+    // matches, which keeps it from hitting the same address in other banks.
+    GameGenie { address: u16, value: u8, compare: Option<u8> },
+    // Pokes RAM once per frame
+    GameShark { bank: Option<u8>, address: u16, value: u8 },
+}
+
+#[derive(Debug, Clone)]
+pub struct Cheat {
+    pub id:          u32,
+    pub code:        String,
+    pub description: String,
+    pub kind:        CheatKind,
+    pub enabled:     bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheatError {
+    InvalidCode(String),
+    UnknownCheat(u32),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::InvalidCode(code) => write!(f, "not a Game Genie or GameShark code: {}", code),
+            CheatError::UnknownCheat(id)  => write!(f, "no cheat with id {}", id),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+// (code, description, enabled) for one line of a cheat file
+type FileEntry<'a> = (&'a str, &'a str, bool);
+
+impl CheatKind {
+    // Game Genie: ABC-DEF or ABC-DEF-GHI. GameShark: 8 hex digits, TTVVLLHH
+    pub fn parse(code: &str) -> Result<CheatKind, CheatError> {
+        let digits: Vec<u8> = code.chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or(CheatError::InvalidCode(code.to_string()))?;
+
+        match digits.len() {
+            6 | 9 => Ok(CheatKind::game_genie(&digits)),
+            8 => CheatKind::game_shark(&digits).ok_or(CheatError::InvalidCode(code.to_string())),
+            _ => Err(CheatError::InvalidCode(code.to_string())),
+        }
+    }
+
+    fn game_genie(d: &[u8]) -> CheatKind {
+        let value   = d[0] << 4 | d[1];
+        let address = ((d[5] ^ 0xF) as u16) << 12 | (d[2] as u16) << 8 | (d[3] as u16) << 4 | d[4] as u16;
+
+        // Digits 7 and 9 make up the compare byte, rotated right by two and scrambled with 0xBA
+        let compare = if d.len() == 9 { Some((d[6] << 4 | d[8]).rotate_right(2) ^ 0xBA) } else { None };
+
+        CheatKind::GameGenie { address, value, compare }
+    }
+
+    fn game_shark(d: &[u8]) -> Option<CheatKind> {
+        let kind    = d[0] << 4 | d[1];
+        let value   = d[2] << 4 | d[3];
+        let address = (d[6] as u16) << 12 | (d[7] as u16) << 8 | (d[4] as u16) << 4 | d[5] as u16;
+
+        // 0x01 writes through the current bank, 0x90-0x97 pick a CGB WRAM bank first
+        let bank = match kind {
+            0x00 | 0x01 => None,
+            0x90..=0x97 => Some(kind & 0x07),
+            _ => return None,
+        };
+
+        Some(CheatKind::GameShark { bank, address, value })
+    }
+}
+
+pub(crate) struct Cheats {
+    list:    Vec<Cheat>,
+    next_id: u32,
+}
+
+impl Cheats {
+    pub fn new() -> Self { Cheats { list: Vec::new(), next_id: 0 } }
+
+    pub fn list(&self) -> &[Cheat] { &self.list }
+
+    pub fn add(&mut self, code: &str, description: &str) -> Result<u32, CheatError> {
+        let kind = CheatKind::parse(code.trim())?;
+        let id   = self.next_id;
+        self.next_id += 1;
+
+        self.list.push(Cheat {
+            id, code: code.trim().to_uppercase(), description: description.to_string(), kind, enabled: true,
+        });
+
+        Ok(id)
+    }
+
+    pub fn remove(&mut self, id: u32) -> Result<Cheat, CheatError> {
+        let index = self.list.iter().position(|c| c.id == id).ok_or(CheatError::UnknownCheat(id))?;
+        Ok(self.list.remove(index))
+    }
+
+    pub fn set_enabled(&mut self, id: u32, enabled: bool) -> Result<(), CheatError> {
+        let cheat = self.list.iter_mut().find(|c| c.id == id).ok_or(CheatError::UnknownCheat(id))?;
+        cheat.enabled = enabled;
+        Ok(())
+    }
+
+    // One code per line, optionally followed by a description. '#' starts a comment and a
+    // leading '-' loads the code disabled. A bad line rejects the whole file, nothing is added.
+    pub fn load_file(&mut self, path: &Path) -> io::Result<Vec<u32>> {
+        let text    = fs::read_to_string(path)?;
+        let entries = Cheats::parse_file(&text)
+            .map_err(|(line, e)| io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", line, e)))?;
+
+        Ok(entries.into_iter().map(|(code, description, enabled)| {
+            let id = self.add(code, description).expect("code was checked while parsing");
+            if !enabled { self.set_enabled(id, false).ok(); }
+            id
+        }).collect())
+    }
+
+    // Every line's entry, or the first bad line number and its error
+    fn parse_file(text: &str) -> Result<Vec<FileEntry<'_>>, (usize, CheatError)> {
+        let mut entries = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue; }
+
+            let (enabled, line) = match line.strip_prefix('-') {
+                Some(rest) => (false, rest.trim_start()),
+                None       => (true, line),
+            };
+            let (code, description) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+
+            CheatKind::parse(code).map_err(|e| (number + 1, e))?;
+            entries.push((code, description.trim(), enabled));
+        }
+
+        Ok(entries)
+    }
+
+    // Called from the ROM read path, so bail out early when there's nothing to do
+    pub fn patch_rom(&self, addr: u16, original: u8) -> u8 {
+        if self.list.is_empty() { return original; }
+
+        for cheat in self.list.iter().filter(|c| c.enabled) {
+            if let CheatKind::GameGenie { address, value, compare } = cheat.kind {
+                if address == addr && compare.is_none_or(|c| c == original) { return value; }
+            }
+        }
+
+        original
+    }
+
+    pub fn ram_writes(&self) -> Vec<(Option<u8>, u16, u8)> {
+        self.list.iter()
+            .filter(|c| c.enabled)
+            .filter_map(|c| match c.kind {
+                CheatKind::GameShark { bank, address, value } => Some((bank, address, value)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_game_genie_codes() {
+        assert_eq!(CheatKind::parse("00A-17B"), Ok(CheatKind::GameGenie { address: 0x4A17, value: 0x00, compare: None }));
+        assert_eq!(CheatKind::parse("00a-17b-c49"), Ok(CheatKind::GameGenie { address: 0x4A17, value: 0x00, compare: Some(0xC8) }));
+        assert!(CheatKind::parse("00A-17B-C").is_err());
+        assert!(CheatKind::parse("00G-17B").is_err());
+    }
+
+    #[test]
+    fn decodes_game_shark_codes() {
+        assert_eq!(CheatKind::parse("010A3CD4"), Ok(CheatKind::GameShark { bank: None, address: 0xD43C, value: 0x0A }));
+        assert_eq!(CheatKind::parse("910A3CD4"), Ok(CheatKind::GameShark { bank: Some(1), address: 0xD43C, value: 0x0A }));
+        assert_eq!(CheatKind::parse("020A3CD4"), Err(CheatError::InvalidCode("020A3CD4".to_string())));
+    }
+
+    #[test]
+    fn applies_enabled_cheats() {
+        let mut cheats = Cheats::new();
+        let genie = cheats.add("00A-17B-C49", "").unwrap();
+        cheats.add("010A3CD4", "").unwrap();
+
+        // The compare byte keeps other banks untouched
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0x00);
+        assert_eq!(cheats.patch_rom(0x4A17, 0x12), 0x12);
+        assert_eq!(cheats.ram_writes(), vec![(None, 0xD43C, 0x0A)]);
+
+        cheats.set_enabled(genie, false).unwrap();
+        assert_eq!(cheats.patch_rom(0x4A17, 0xC8), 0xC8);
+        assert_eq!(cheats.remove(genie + 5).unwrap_err(), CheatError::UnknownCheat(genie + 5));
+    }
+
+    #[test]
+    fn parses_cheat_files() {
+        let text = "# Infinite lives\n010A3CD4 lives\n\n- 00A-17B-C49 Start with 10\n";
+        assert_eq!(Cheats::parse_file(text), Ok(vec![("010A3CD4", "lives", true), ("00A-17B-C49", "Start with 10", false)]));
+    }
+
+    #[test]
+    fn rejects_cheat_files_atomically() {
+        let path = std::env::temp_dir().join(format!("cheats-{}.cht", std::process::id()));
+        fs::write(&path, "010A3CD4 lives\nNOTACODE\n00A-17B-C49\n").unwrap();
+
+        let mut cheats = Cheats::new();
+        let error = cheats.load_file(&path).unwrap_err();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().starts_with("line 2:"));
+        assert!(cheats.list().is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use std::str::FromStr;
+
+use crate::{cartridge::CartridgeHeader, utils::rgb555};
+
+// The CGB boot ROM colors DMG-only carts: Nintendo titles get a palette picked by title
+// checksum, everything else the default. Holding a direction (+A/B) during the logo overrides
+// the pick; here that's a config setting instead.
+
+// BG, OBJ0 and OBJ1 colors for shades 0-3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmgColors {
+    pub bg:   [u32; 4],
+    pub obj0: [u32; 4],
+    pub obj1: [u32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    Up, UpA, UpB, Left, LeftA, LeftB,
+    Down, DownA, DownB, Right, RightA, RightB,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colorization {
+    Off,                // Plain grays, like a DMG
+    Auto,               // What the CGB boot ROM would pick
+    Preset(Preset),     // A button combo palette
+}
+
+// The boot ROM's base palettes, RGB555. Combinations point at a color rather than a palette, and
+// a few of them start partway into one.
+const PALETTES: [u16; 30 * 4] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000,   0x639F, 0x4279, 0x15B0, 0x04CB,   0x7FFF, 0x6E31, 0x454A, 0x0000,
+    0x7FFF, 0x1BEF, 0x0200, 0x0000,   0x7FFF, 0x421F, 0x1CF2, 0x0000,   0x7FFF, 0x5294, 0x294A, 0x0000,
+    0x7FFF, 0x03FF, 0x012F, 0x0000,   0x7FFF, 0x03EF, 0x01D6, 0x0000,   0x7FFF, 0x42B5, 0x3DC8, 0x0000,
+    0x7E74, 0x03FF, 0x0180, 0x0000,   0x67FF, 0x77AC, 0x1A13, 0x2D6B,   0x7ED6, 0x4BFF, 0x2175, 0x0000,
+    0x53FF, 0x4A5F, 0x7E52, 0x0000,   0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0,   0x03ED, 0x7FFF, 0x255F, 0x0000,
+    0x036A, 0x021F, 0x03FF, 0x7FFF,   0x7FFF, 0x01DF, 0x0112, 0x0000,   0x231F, 0x035F, 0x00F2, 0x0009,
+    0x7FFF, 0x03EA, 0x011F, 0x0000,   0x299F, 0x001A, 0x000C, 0x0000,   0x7FFF, 0x027F, 0x001F, 0x0000,
+    0x7FFF, 0x03E0, 0x0206, 0x0120,   0x7FFF, 0x7EEB, 0x001F, 0x7C00,   0x7FFF, 0x3FFF, 0x7E00, 0x001F,
+    0x7FFF, 0x03FF, 0x001F, 0x0000,   0x03FF, 0x001F, 0x000C, 0x0000,   0x7FFF, 0x033F, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037F, 0x7FFF,   0x7FFF, 0x7E8C, 0x7C00, 0x0000,   0x7FFF, 0x1BEF, 0x6180, 0x0000,
+];
+
+// OBJ0, OBJ1 and BG of each combination, as the index of their first color in PALETTES
+const fn combination(obj0: u8, obj1: u8, bg: u8) -> (u8, u8, u8) { (obj0 * 4, obj1 * 4, bg * 4) }
+
+const COMBINATIONS: [(u8, u8, u8); 51] = [
+    combination( 4,  4, 29), combination(18, 18, 18), combination(20, 20, 20), combination(24, 24, 24),
+    combination( 9,  9,  9), combination( 0,  0,  0), combination(27, 27, 27), combination( 5,  5,  5),
+    combination(12, 12, 12), combination(26, 26, 26), combination(16,  8,  8), combination( 4, 28, 28),
+    combination( 4,  2,  2), combination( 3,  4,  4), combination( 4, 29, 29), combination(28,  4, 28),
+    combination( 2, 17,  2), combination(16, 16,  8), combination( 4,  4,  7), combination( 4,  4, 18),
+    combination( 4,  4, 20), combination(19, 19,  9), (15, 15, 44),            combination(17, 17,  2),
+    combination( 4,  4,  2), combination( 4,  4,  3), combination(28, 28,  0), combination( 3,  3,  0),
+    combination( 0,  0,  1), combination(18, 22, 18), combination(20, 22, 20), combination(24, 22, 24),
+    combination(16, 22,  8), combination(17,  4, 13), (111, 0, 56),            (111, 16, 60),
+    combination(19, 22,  9), combination(16, 28, 10), combination( 4, 23, 28), combination(17, 22,  2),
+    combination( 4,  0,  2), combination( 4, 28,  3), combination(28,  3,  0), combination( 3, 28,  4),
+    combination(21, 28,  4), combination( 3, 28,  0), combination(25,  3, 28), combination( 0, 28,  8),
+    combination( 4,  3, 28), combination(28,  3,  6), combination( 4, 28, 29),
+];
+
+// Title checksums of the Nintendo games the boot ROM knows, and the combination each one gets.
+// Checksums from FIRST_SHARED on belong to more than one game, so the 4th title letter has to
+// match TITLE_LETTERS as well.
+const TITLE_CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B,
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4,
+    0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4,
+    0xB3,
+];
+
+const FIRST_SHARED:  usize = 65;
+const TITLE_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+const TITLE_COMBINATIONS: [u8; 94] = [
+     0,  4,  5, 35, 34,  3, 31, 15, 10,  5, 19, 36,  7, 37, 30, 44,
+    21, 32, 31, 20,  5, 33, 13, 14,  5, 29,  5, 18,  9,  3,  2, 26,
+    25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34,
+     5, 42,  6,  5, 33, 25, 42, 42, 40,  2, 16, 25, 42, 42,  5,  0,
+    39,
+    36, 22, 25,  6, 32, 12, 36, 11, 39, 18, 39, 24, 31, 50,
+    17, 46,  6, 27,  0, 47, 41, 41,  0,  0, 19, 34, 23, 18,
+    29,
+];
+
+fn combination_colors(index: usize) -> DmgColors {
+    let (obj0, obj1, bg) = COMBINATIONS[index];
+    let palette = |start: u8| {
+        let mut colors = [0; 4];
+        for (i, color) in colors.iter_mut().enumerate() { *color = rgb555(PALETTES[start as usize + i]); }
+        colors
+    };
+
+    DmgColors { bg: palette(bg), obj0: palette(obj0), obj1: palette(obj1) }
+}
+
+impl Preset {
+    pub fn colors(&self) -> DmgColors {
+        use Preset::*;
+
+        combination_colors(match self {
+            Up    => 5,  UpA    => 43, UpB    => 28,
+            Left  => 48, LeftA  => 40, LeftB  => 7,
+            Down  => 8,  DownA  => 3,  DownB  => 49,
+            Right => 0,  RightA => 1,  RightB => 6,
+        })
+    }
+}
+
+// Only licensed Nintendo games are looked up; anything else gets combination 0, same as Right
+fn title_colors(header: &CartridgeHeader) -> DmgColors {
+    if header.licensee != "01" { return combination_colors(0); }
+
+    let index = TITLE_CHECKSUMS.iter().enumerate().position(|(i, &sum)| {
+        sum == header.title_checksum && (i < FIRST_SHARED || TITLE_LETTERS[i - FIRST_SHARED] == header.title_fourth)
+    });
+
+    combination_colors(index.map_or(0, |i| TITLE_COMBINATIONS[i] as usize))
+}
+
+impl Colorization {
+    pub fn colors(&self, header: &CartridgeHeader) -> Option<DmgColors> {
+        match self {
+            Colorization::Off            => None,
+            Colorization::Auto           => Some(title_colors(header)),
+            Colorization::Preset(preset) => Some(preset.colors()),
+        }
+    }
+}
+
+// "auto", "off", or a button combo such as "left+b"
+impl FromStr for Colorization {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Preset::*;
+
+        Ok(Colorization::Preset(match s.to_ascii_lowercase().as_str() {
+            "auto"    => return Ok(Colorization::Auto),
+            "off"     => return Ok(Colorization::Off),
+            "up"      => Up,    "up+a"    => UpA,    "up+b"    => UpB,
+            "left"    => Left,  "left+a"  => LeftA,  "left+b"  => LeftB,
+            "down"    => Down,  "down+a"  => DownA,  "down+b"  => DownB,
+            "right"   => Right, "right+a" => RightA, "right+b" => RightB,
+            _ => return Err(format!("unknown palette {} (expected auto, off, or a combo like left+b)", s)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::tests::{rom_image, fix_checksum};
+
+    fn header(title: &[u8], licensee: u8) -> CartridgeHeader {
+        let mut rom = rom_image(0x00, 0x00, 0x00);
+        rom[0x134..0x144].fill(0);
+        rom[0x134..0x134 + title.len()].copy_from_slice(title);
+        rom[0x14B] = licensee;
+        fix_checksum(&mut rom);
+        CartridgeHeader::parse(&rom).unwrap()
+    }
+
+    const RED:   [u32; 4] = [0xFFFFFF, 0xFF8484, 0x943939, 0x000000];
+    const GREEN: [u32; 4] = [0xFFFFFF, 0x7BFF31, 0x008400, 0x000000];
+    const BLUE:  [u32; 4] = [0xFFFFFF, 0x63A5FF, 0x0000FF, 0x000000];
+
+    #[test]
+    fn presets_match_the_button_combos() {
+        assert_eq!(Preset::UpA.colors(), DmgColors { bg: RED, obj0: GREEN, obj1: BLUE });
+        assert_eq!(Preset::Right.colors(), DmgColors { bg: [0xFFFFFF, 0x7BFF31, 0x0063C6, 0x000000], obj0: RED, obj1: RED });
+        assert_eq!(Preset::RightB.colors().bg, [0x000000, 0x008484, 0xFFDE00, 0xFFFFFF]);
+    }
+
+    #[test]
+    fn picks_palettes_by_title() {
+        let auto = |title: &[u8], licensee| Colorization::Auto.colors(&header(title, licensee)).unwrap();
+
+        assert_eq!(auto(b"TETRIS", 0x01), Preset::DownA.colors());
+        assert_eq!(auto(b"POKEMON RED", 0x01), DmgColors { bg: RED, obj0: GREEN, obj1: RED });
+        assert_eq!(auto(b"POKEMON BLUE", 0x01), DmgColors { bg: BLUE, obj0: RED, obj1: BLUE });
+
+        // Same checksum as POKEMON BLUE, told apart by the 4th letter
+        assert_eq!(auto(b"VEGAS STAKES", 0x01), DmgColors { bg: GREEN, obj0: RED, obj1: BLUE });
+        assert_eq!(auto(b"AAAZD", 0x01), Preset::Right.colors());
+
+        // Third party games get the default
+        assert_eq!(auto(b"TETRIS", 0x08), Preset::Right.colors());
+    }
+}
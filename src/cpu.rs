@@ -6,6 +6,7 @@ use std::cell::RefCell;
 use crate::{
     instructions::{AllInstructions, AllRegisters, FlagChecks, InterruptIDs, RstParameters}, 
     memory::MemoryBus, 
+    model::PostBoot,
     registers::{FlagsRegister, Registers}
 };
 
@@ -14,6 +15,7 @@ pub(crate) struct CPU {
     bus: Rc<RefCell<MemoryBus>>,      // Memory Bus; memory.rs
 
     halted:      bool,
+    stall:       u16,               // Extra T-cycles owed by the last instruction (speed switch)
 }
 
 const IE_REGISTER_BYTE_LOCATION: u16 = 0xFFFF;
@@ -26,9 +28,28 @@ impl CPU {
             registers   : Registers::new(),
             bus         : mem, 
             halted      : false,
+            stall       : 0,
         }
     }
 
+    // Start at the cartridge entry point as if the boot ROM had just finished
+    pub fn skip_boot(&mut self, state: &PostBoot) {
+        self.registers.set_af(state.af);
+        self.registers.set_bc(state.bc);
+        self.registers.set_de(state.de);
+        self.registers.set_hl(state.hl);
+
+        let mut bus = self.bus.borrow_mut();
+        bus.sp = state.sp;
+        bus.pc = 0x0100;
+    }
+
+    pub fn reset(&mut self) {
+        self.registers = Registers::new();
+        self.halted    = false;
+        self.stall     = 0;
+    }
+
     pub fn step(&mut self) -> u16 {
         // Only execute if not halted
         if self.halted { return 0; }
@@ -45,7 +66,9 @@ impl CPU {
         let Some(instruction) = AllInstructions::decode(instruction_byte, prefixed) else { todo!("do nothing!") };
         println!("Executing...");
 
-        (self.execute(instruction) * 4).into()
+        let cycles = self.execute(instruction) as u16 * 4;
+        let dma = std::mem::take(&mut self.bus.borrow_mut().dma_stall);
+        cycles + std::mem::take(&mut self.stall) + dma
     }
 
     pub fn check_for_interrupts(&mut self) {        
@@ -82,7 +105,7 @@ impl CPU {
             NOP     => { 1 }
             EMPTY   => { 0 }
             HALT    => { self.halted = true; 1 }
-            STOP    => { 1 }
+            STOP    => { self.stop() }
             DI      => { self.bus.borrow_mut().ime = false; 1 }
             EI      => { self.bus.borrow_mut().ime = true; 1 }
 
@@ -291,6 +314,7 @@ impl CPU {
                 let ogval = self.get_register_u16(target);
                 let value = self.inc_16(ogval);
                 self.set_register_u16(target, value);
+                self.bus.borrow_mut().trigger_oam_bug(ogval);
 
                 2
             },
@@ -306,6 +330,7 @@ impl CPU {
                 let ogval = self.get_register_u16(target);
                 let value = self.dec_16(ogval);
                 self.set_register_u16(target, value);
+                self.bus.borrow_mut().trigger_oam_bug(ogval);
 
                 2
             },
@@ -674,10 +699,21 @@ impl CPU {
         new_value
     }
 
+    // STOP is followed by a padding byte. On CGB with KEY1 armed it switches speed, stalling
+    // the CPU for 2050 M-cycles while the clock settles; low power mode isn't emulated.
+    fn stop(&mut self) -> u8 {
+        let mut bus = self.bus.borrow_mut();
+        bus.read_increment();
+
+        if bus.switch_speed() { self.stall = 2050 * 4; }
+        1
+    }
+
     fn inchl(&mut self) {
         let hlval = self.get_register_u16(AllRegisters::HL);
         let value = self.inc_16(hlval);
         self.set_register_u16(AllRegisters::HL, value);
+        self.bus.borrow_mut().trigger_oam_bug(hlval);
     }
 
     fn dec(&mut self, value: u8) -> u8 {
@@ -700,6 +736,7 @@ impl CPU {
         let hlval = self.get_register_u16(AllRegisters::HL);
         let value = self.dec_16(hlval);
         self.set_register_u16(AllRegisters::HL, value);
+        self.bus.borrow_mut().trigger_oam_bug(hlval);
     }
 
     fn handle_load(&mut self, to: AllRegisters, from: AllRegisters) {
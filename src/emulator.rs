@@ -1,51 +1,28 @@
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::Duration;
 
-use std::{fs::File, io::Read};
-
-use crate::{ cpu::CPU, memory::MemoryBus, ppu::PPU, input::IPU, timer::Timer}; //, apu::APU };
+use crate::{ boot::BootRom, ppu::FrameSink, cartridge::{ROM, RomError, CartridgeEvent, CgbSupport}, cheats::{Cheat, CheatError}, colorize::Colorization, search::{RamSearch, Candidate, Comparison, Width}, mbc::rtc::ClockSource, cpu::CPU, input::Button, memory::MemoryBus, model::Model, ppu::PPU, input::IPU, timer::Timer}; //, apu::APU };
 use winit::{
     window::{Window, WindowBuilder},
     event_loop::{EventLoop, ControlFlow},
     platform::run_return::EventLoopExtRunReturn,
     dpi::LogicalSize,
 }; 
-use pixels::{Pixels, SurfaceTexture, wgpu::Backends};
-
-pub(crate) struct ROM {
-    bytes: Vec<u8>,
-    bank: u8,
-}
+use pixels::{Pixels, SurfaceTexture};
 
-impl ROM {
-    pub fn new(path: &str) -> Self {
-        let mut buffer  = Vec::new();
-        let mut file    = File::open(path).expect("Invalid ROM path");
-        file.read(&mut buffer).expect("Unable to read ROM");
+const CHEAT_EXTENSION: &str = "cht";
 
-        ROM { bytes: buffer, bank: 1, }
-    }
-
-    pub fn read_byte(&self, addr: u16) -> u8 {
-        match addr {
-            0x0000..=0x3FFF => { self.bytes[addr as usize] },
-            0x4000..=0x7FFF => { 
-                let addr = self.bank as u32 * 0x4000 + (addr - 0x4000) as u32;
-                self.bytes[addr as usize]
-            }
-            _ => 0xFF,
-        }
-    }
-
-    pub fn switch_bank(&mut self, bank: u8) {
-        self.bank = bank;
-    }
-}
+// Oldest events get dropped if the host never drains the queue
+const MAX_PENDING_EVENTS: usize = 256;
 
-pub(crate) struct Screen {
+// A winit window showing frames through pixels
+pub struct Screen {
     pub dsp: Window,
     // pub evt: EventLoop<()>,
-    pub pxl: Rc<RefCell<Pixels>>,
+    pub pxl: Pixels,
 }
 
 impl Screen {
@@ -58,54 +35,250 @@ impl Screen {
 
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        let pixels = Pixels::new_with_backends(160, 144, surface_texture, pixels::wgpu::Backends::all());
+        let pixels = Pixels::new(160, 144, surface_texture);
 
         Screen {
-            dsp: window, pxl: Rc::new(RefCell::new(pixels.unwrap())),
+            dsp: window, pxl: pixels.unwrap(),
         }
     }
 }
 
-pub(crate) struct Emulator {
-    pub cpu: CPU,
+impl FrameSink for Screen {
+    fn present(&mut self, frame: &[u32], width: usize, height: usize) {
+        if self.pxl.get_frame().len() != width * height * 4 { self.pxl.resize_buffer(width as u32, height as u32); }
+
+        for (pixel, color) in self.pxl.get_frame().chunks_exact_mut(4).zip(frame) {
+            pixel[0] = (color >> 16) as u8;
+            pixel[1] = (color >> 8)  as u8;
+            pixel[2] = (*color)      as u8;
+            pixel[3] = 0xFF;
+        }
+
+        self.pxl.render().expect("Failed to render frame");
+    }
+}
+
+// Loading doesn't open a window; hand a FrameSink (such as Screen) to set_frame_sink to see frames
+pub struct Emulator {
+    pub(crate) cpu: CPU,
     // // pub apu: APU, 
-    pub ppu: PPU,
-    pub ipu: IPU,
-    pub tmr: Timer,
+    pub(crate) ppu: PPU,
+    pub(crate) ipu: IPU,
+    pub(crate) tmr: Timer,
+    pub(crate) mem: Rc<RefCell<MemoryBus>>,
+
+    model:  Model,
+    events: VecDeque<CartridgeEvent>,
+    search: Option<RamSearch>,
 
     // pub dsp: Screen,
 }
 
 impl Emulator {
-    pub fn new(_loop: &EventLoop<()>) -> Self {
-        println!("In emulator::new()");
-        let rom = ROM::new("roms\\game.gb");
-        let mem = Rc::new(RefCell::new(MemoryBus::new(rom)));
-        
-        let dsp = Screen::new(_loop);
+    pub fn from_rom_path<P: AsRef<Path>>(path: P) -> Result<Self, RomError> {
+        Ok(Emulator::new(ROM::from_path(&path)?).with_cheat_file(path.as_ref()))
+    }
+
+    pub fn from_archive_entry<P: AsRef<Path>>(path: P, entry: &str) -> Result<Self, RomError> {
+        Ok(Emulator::new(ROM::from_archive_entry(&path, Some(entry))?).with_cheat_file(path.as_ref()))
+    }
 
-        Emulator {
+    pub fn from_rom_path_with_patch<P: AsRef<Path>, Q: AsRef<Path>>(path: P, patch: Q) -> Result<Self, RomError> {
+        Ok(Emulator::new(ROM::open(path.as_ref(), None, Some(patch.as_ref()))?).with_cheat_file(path.as_ref()))
+    }
+
+    pub fn from_rom_bytes(bytes: Vec<u8>) -> Result<Self, RomError> {
+        Ok(Emulator::new(ROM::from_bytes(bytes)?))
+    }
+
+    // Pick up <rom>.cht if the game has one; a broken cheat file shouldn't keep the game from starting
+    fn with_cheat_file(mut self, rom_path: &Path) -> Self {
+        let path = rom_path.with_extension(CHEAT_EXTENSION);
+        if path.is_file() {
+            if let Err(e) = self.load_cheat_file(&path) {
+                eprintln!("Unable to load cheats from {}: {}", path.display(), e);
+            }
+        }
+
+        self
+    }
+
+    fn new(rom: ROM) -> Self {
+        let mem   = Rc::new(RefCell::new(MemoryBus::new(rom)));
+        let model = mem.borrow().model;
+
+        let mut emulator = Emulator {
             cpu: CPU::new(Rc::clone(&mem)),
             // // apu: apu,
-            ppu: PPU::new(Rc::clone(&mem), Rc::clone(&dsp.pxl)),
+            ppu: PPU::new(Rc::clone(&mem)),
             ipu: IPU::new(Rc::clone(&mem)),
             tmr: Timer::new(Rc::clone(&mem)),
+            mem: mem,
+
+            model:  model,
+            events: VecDeque::new(),
+            search: None,
             
             // dsp: dsp,
-        }
+        };
+
+        // Without a boot ROM the cartridge starts straight at 0x0100
+        emulator.skip_boot();
+        emulator
+    }
+
+    pub fn set_frame_sink(&mut self, sink: Box<dyn FrameSink>) { self.ppu.set_frame_sink(sink); }
+
+    // Last finished frame as 0xRRGGBB pixels, for hosts that poll instead of using a sink
+    pub fn frame(&self) -> &[u32] { self.ppu.frame() }
+
+    pub fn model(&self) -> Model { self.model }
+
+    // How DMG-only games get colored when running on a CGB or AGB
+    pub fn set_colorization(&mut self, colorization: Colorization) {
+        self.ppu.set_colorization(colorization);
+    }
+
+    pub fn double_speed(&self) -> bool { self.mem.borrow().double_speed }
+
+    // Override the model picked from the cartridge header. Unless a boot ROM is running, the
+    // machine restarts in the new model's post-boot state.
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+        self.mem.borrow_mut().set_model(model);
+
+        if !self.mem.borrow().boot_rom_mapped() { self.skip_boot(); }
+    }
+
+    pub fn skip_boot(&mut self) {
+        let model = self.model;
+        let (checksum, cgb_game) = {
+            let bus = self.mem.borrow();
+            (bus.rom.header.header_checksum, bus.rom.header.cgb != CgbSupport::None)
+        };
+
+        let state = model.post_boot(checksum, cgb_game);
+        self.mem.borrow_mut().load_io(&model.post_boot_io());
+        self.cpu.skip_boot(&state);
+        self.tmr.set_divider(state.div);
     }
 
     fn step(&mut self) {
+        let speed  = self.mem.borrow().double_speed;
         let cycles = self.cpu.step();
 
+        // STOP resets DIV, and the speed switch is the only STOP we act on
+        if self.mem.borrow().double_speed != speed { self.tmr.set_divider(0); }
+
+        // The timer runs off the CPU clock; the PPU, APU and cartridge clock don't speed up
+        let dots = if self.mem.borrow().double_speed { cycles / 2 } else { cycles };
+
         self.tmr.step(cycles);
-        // self.ppu.update(cycles);
-        // self.apu.update(cycles);
+        self.mem.borrow_mut().step_oam_dma(cycles / 4);
+        self.mem.borrow_mut().rom.step(dots);
+        self.ppu.update(dots);
+        // self.apu.update(dots);
         self.cpu.check_for_interrupts();
     }
 
-    fn process_events(&self) {
+    pub fn add_cheat(&mut self, code: &str, description: &str) -> Result<u32, CheatError> {
+        self.mem.borrow_mut().cheats.add(code, description)
+    }
+
+    pub fn remove_cheat(&mut self, id: u32) -> Result<Cheat, CheatError> {
+        self.mem.borrow_mut().cheats.remove(id)
+    }
+
+    pub fn set_cheat_enabled(&mut self, id: u32, enabled: bool) -> Result<(), CheatError> {
+        self.mem.borrow_mut().cheats.set_enabled(id, enabled)
+    }
+
+    pub fn cheats(&self) -> Vec<Cheat> {
+        self.mem.borrow().cheats.list().to_vec()
+    }
+
+    pub fn load_cheat_file<P: AsRef<Path>>(&mut self, path: P) -> std::io::Result<Vec<u32>> {
+        self.mem.borrow_mut().cheats.load_file(path.as_ref())
+    }
+
+    // Snapshot cart RAM, WRAM and HRAM as the starting point of a new search
+    pub fn start_ram_search(&mut self, width: Width, signed: bool) -> usize {
+        let search = RamSearch::start(&self.mem.borrow(), width, signed);
+        let count  = search.candidates().len();
+        self.search = Some(search);
+
+        count
+    }
+
+    // Narrow the search down against the values seen on the previous pass
+    pub fn filter_ram_search(&mut self, cmp: Comparison) -> usize {
+        match self.search.as_mut() {
+            Some(search) => search.filter(&self.mem.borrow(), cmp),
+            None => 0,
+        }
+    }
+
+    pub fn ram_search_results(&self) -> &[Candidate] {
+        self.search.as_ref().map_or(&[], |s| s.candidates())
+    }
 
+    pub fn export_ram_search<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        match self.search.as_ref() {
+            Some(search) => search.write_watch_list(path.as_ref()),
+            None => Ok(()),
+        }
+    }
+
+    // Run a user-supplied DMG/MGB/SGB/CGB boot ROM before the cartridge
+    pub fn load_boot_rom<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RomError> {
+        let boot = BootRom::from_path(path.as_ref())?;
+        self.mem.borrow_mut().map_boot_rom(boot);
+        self.cpu.reset();
+        self.tmr.set_divider(0);
+
+        Ok(())
+    }
+
+    // Which archive member the ROM was unpacked from, if it came out of a zip or gzip file
+    pub fn rom_entry(&self) -> Option<String> {
+        self.mem.borrow().rom.entry.clone()
+    }
+
+    // Pick where the cartridge clock gets its time from; Emulated keeps test runs deterministic
+    pub fn set_rtc_source(&mut self, source: ClockSource) {
+        if let Some(rtc) = self.mem.borrow_mut().rom.rtc() { rtc.set_source(source); }
+    }
+
+    // Light arriving at the cart's IR sensor (HuC1/HuC3); the LED side is reported through poll_event
+    pub fn set_infrared_input(&mut self, light: bool) {
+        if let Some(ir) = self.mem.borrow_mut().rom.infrared() { ir.light = light; }
+    }
+
+    // Tilt in g for MBC7 carts; frontends can drive this from the mouse, keys or a script
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        if let Some(accel) = self.mem.borrow_mut().rom.accelerometer() { accel.x = x; accel.y = y; }
+    }
+
+    // How often dirty battery RAM gets written back to the .sav file
+    pub fn set_save_interval(&mut self, interval: Duration) {
+        self.mem.borrow_mut().rom.set_save_interval(interval);
+    }
+
+    // Joypads 2-4 for SGB games that ask for multiplayer with MLT_REQ
+    pub fn set_joypad(&mut self, player: usize, button: Button, pressed: bool) {
+        self.ipu.set_player(player, button, pressed);
+    }
+
+    // Hosts drain this to react to cartridge hardware (vibrate a gamepad on rumble, ...)
+    pub fn poll_event(&mut self) -> Option<CartridgeEvent> {
+        self.events.pop_front()
+    }
+
+    fn process_events(&mut self) {
+        while let Some(event) = self.mem.borrow_mut().rom.poll_event() {
+            if self.events.len() == MAX_PENDING_EVENTS { self.events.pop_front(); }
+            self.events.push_back(event);
+        }
     }
 
     pub fn run(&mut self, _loop: &mut EventLoop<()>) { //&mut self) {
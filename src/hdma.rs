@@ -0,0 +1,77 @@
+// CGB VRAM DMA (HDMA1-HDMA5). The bus does the copying, this only keeps track of the registers.
+pub const HDMA1: u16 = 0xFF51;  // Source, high
+pub const HDMA2: u16 = 0xFF52;  // Source, low (lower 4 bits ignored)
+pub const HDMA3: u16 = 0xFF53;  // Destination, high (only bits 0-4, always in VRAM)
+pub const HDMA4: u16 = 0xFF54;  // Destination, low (lower 4 bits ignored)
+pub const HDMA5: u16 = 0xFF55;  // Length / mode / start
+
+pub const BLOCK_SIZE: u16 = 0x10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HdmaMode { General, HBlank }
+
+pub struct Hdma {
+    pub src:       u16,
+    pub dst:       u16,
+    pub remaining: u8,      // Blocks left minus one, the way HDMA5 reports it
+    pub active:    bool,    // An HBlank transfer is in progress
+}
+
+impl Hdma {
+    pub fn new() -> Self {
+        Hdma { src: 0x0000, dst: 0x8000, remaining: 0x7F, active: false }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            // Bit 7 clear while an HBlank transfer runs; 0xFF once it's done, 0x80 | left after a cancel
+            HDMA5 => { if self.active { self.remaining } else { 0x80 | self.remaining } }
+            _     => { 0xFF } // The address registers are write-only
+        }
+    }
+
+    // Returns the kind of transfer an HDMA5 write kicked off, if any
+    pub fn write(&mut self, addr: u16, val: u8) -> Option<HdmaMode> {
+        match addr {
+            HDMA1 => { self.src = (self.src & 0x00F0) | (val as u16) << 8 }
+            HDMA2 => { self.src = (self.src & 0xFF00) | (val & 0xF0) as u16 }
+            HDMA3 => { self.dst = 0x8000 | (self.dst & 0x00F0) | ((val & 0x1F) as u16) << 8 }
+            HDMA4 => { self.dst = (self.dst & 0xFF00) | (val & 0xF0) as u16 }
+            HDMA5 => {
+                // Writing bit 7 clear during an HBlank transfer cancels it
+                if self.active && val & 0x80 == 0 {
+                    self.active = false;
+                    return None;
+                }
+
+                self.remaining = val & 0x7F;
+                if val & 0x80 == 0 { return Some(HdmaMode::General); }
+
+                self.active = true;
+                return Some(HdmaMode::HBlank);
+            }
+            _ => { }
+        }
+
+        None
+    }
+
+    // Step past one copied block; true once the last one is done
+    pub fn advance(&mut self) -> bool {
+        self.src = self.src.wrapping_add(BLOCK_SIZE);
+        self.dst = 0x8000 | (self.dst.wrapping_add(BLOCK_SIZE) & 0x1FF0);
+
+        if self.remaining == 0 {
+            self.remaining = 0x7F;
+            self.active    = false;
+            return true;
+        }
+
+        self.remaining -= 1;
+        false
+    }
+}
+
+impl Default for Hdma {
+    fn default() -> Self { Hdma::new() }
+}
@@ -23,46 +23,43 @@ pub enum Button {
 }
 
 pub(crate) struct IPU {
-    buttons: [bool; 8],
+    buttons: [[bool; 8]; 4],            // One set per joypad; SGB multiplayer reads all four
     mem: Rc<RefCell<MemoryBus>>,                              
 }
 
 impl IPU {
     pub fn new(mem: Rc<RefCell<MemoryBus>>) -> Self{
         IPU {
-            buttons: [false; 8],
+            buttons: [[false; 8]; 4],
             mem: mem,
         }        
     }
 
-    fn get(&self, key: Button) -> bool {
-        self.buttons[key as usize]
+    fn get(&self, player: usize, key: Button) -> bool {
+        self.buttons[player][key as usize]
     }
 
     fn set(&mut self, key: Button, pressed: bool) {
-        self.buttons[key as usize] = pressed;
+        self.buttons[0][key as usize] = pressed;
     }
 
+    // Joypads 2-4 only show up once an SGB game turns on multiplayer
+    pub fn set_player(&mut self, player: usize, key: Button, pressed: bool) {
+        if let Some(buttons) = self.buttons.get_mut(player) { buttons[key as usize] = pressed; }
+        self.update_byte();
+    }
+
+    // Hand the bus each joypad's state in JOYP bit order: directions in the low nibble, buttons
+    // in the high one, set while pressed. JOYP reads are worked out from it on the spot.
     fn update_byte(&self) {
         use Button::*;
 
-        let mut curr = self.mem.borrow().read_byte(0xFF00);
-
-        if curr & 0x10 != 0 { // Directional Keys
-            curr |= if self.get(Down)   { 0x8 } else { 0x0 };
-            curr |= if self.get(Up)     { 0x4 } else { 0x0 };
-            curr |= if self.get(Left)   { 0x2 } else { 0x0 };
-            curr |= if self.get(Right)  { 0x1 } else { 0x0 };
-        }
-        else if curr & 0x20 != 0 { // Button Keys
-            curr |= if self.get(Start)  { 0x8 } else { 0x0 };
-            curr |= if self.get(Select) { 0x4 } else { 0x0 };
-            curr |= if self.get(B)      { 0x2 } else { 0x0 };
-            curr |= if self.get(A)      { 0x1 } else { 0x0 };
+        let mut mem = self.mem.borrow_mut();
+        for player in 0..self.buttons.len() {
+            let keys = [Right, Left, Up, Down, A, B, Select, Start];
+            mem.joypad[player] = keys.into_iter().enumerate()
+                .fold(0, |state, (bit, key)| if self.get(player, key) { state | 1 << bit } else { state });
         }
-        else { curr = 0xC0; }
-
-        self.mem.borrow_mut().write_byte(0xFF00, curr);
     }
 
     pub fn poll(&mut self, event: &Event<()>) {
@@ -1,12 +1,25 @@
 // src/lib.rs
 
 pub mod apu;
+pub mod archive;
+pub mod boot;
+pub mod cartridge;
+pub mod cheats;
+pub mod colorize;
 pub mod cpu;
 pub mod emulator;
+pub mod hdma;
 pub mod input;
 pub mod instructions;
+pub mod mbc;
 pub mod memory;
+pub mod model;
+pub mod oam_dma;
+pub mod patch;
 pub mod ppu;
 pub mod registers;
+pub mod save;
+pub mod search;
+pub mod sgb;
 pub mod timer;
 pub mod utils;
\ No newline at end of file
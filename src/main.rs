@@ -2,23 +2,81 @@
 #![allow(non_snake_case)]
 
 // pub mod apu;
+pub mod archive;
+pub mod boot;
+pub mod cartridge;
+pub mod cheats;
+pub mod colorize;
 pub mod cpu;
 pub mod emulator;
+pub mod hdma;
 pub mod input;
 pub mod instructions;
+pub mod mbc;
 pub mod memory;
+pub mod model;
+pub mod oam_dma;
+pub mod patch;
 pub mod ppu;
 pub mod registers;
+pub mod save;
+pub mod search;
+pub mod sgb;
 pub mod timer;
 pub mod utils;
 
-use emulator::Emulator;
+use colorize::Colorization;
+use emulator::{Emulator, Screen};
+use model::Model;
 use winit::event_loop::EventLoop;
 
+const DEFAULT_ROM: &str = "roms/game.gb";
+
 fn main() {
     let mut event_loop = EventLoop::new();
 
-    let mut emulator   = Emulator::new(&event_loop);
+    // emulator [--boot <boot rom>] [--model <dmg0|dmg|mgb|sgb|cgb|agb>] [--palette <auto|off|combo>] [rom]
+    let mut path    = DEFAULT_ROM.to_string();
+    let mut boot    = None;
+    let mut model   = None;
+    let mut palette = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--boot"    => boot = args.next(),
+            "--model"   => match args.next().map(|m| m.parse::<Model>()) {
+                Some(Ok(m))  => model = Some(m),
+                Some(Err(e)) => { eprintln!("{}", e); std::process::exit(1); }
+                None         => { eprintln!("--model needs a value"); std::process::exit(1); }
+            },
+            "--palette" => match args.next().map(|p| p.parse::<Colorization>()) {
+                Some(Ok(p))  => palette = Some(p),
+                Some(Err(e)) => { eprintln!("{}", e); std::process::exit(1); }
+                None         => { eprintln!("--palette needs a value"); std::process::exit(1); }
+            },
+            _           => path = arg,
+        }
+    }
+
+    let mut emulator = match Emulator::from_rom_path(&path) {
+        Ok(emulator) => emulator,
+        Err(e) => {
+            eprintln!("Unable to load {}: {}", path, e);
+            std::process::exit(1);
+        }
+    };
+
+    emulator.set_frame_sink(Box::new(Screen::new(&event_loop)));
+
+    if let Some(model) = model { emulator.set_model(model); }
+    if let Some(palette) = palette { emulator.set_colorization(palette); }
+
+    if let Some(boot) = boot {
+        if let Err(e) = emulator.load_boot_rom(&boot) {
+            eprintln!("Unable to load boot ROM {}: {}", boot, e);
+            std::process::exit(1);
+        }
+    }
     // println!("rom loaded!");
     
     // emulator.run(&mut event_loop);
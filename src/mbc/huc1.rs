@@ -0,0 +1,58 @@
+use std::collections::VecDeque;
+
+use crate::cartridge::CartridgeEvent;
+use crate::mbc::{Mapper, Infrared, ram_offset, read_banked};
+
+pub(crate) struct HuC1 {
+    ir_mode:  bool,     // 0x0E in 0x0000-0x1FFF swaps cart RAM for the IR port
+    rom_bank: u8,
+    ram_bank: u8,
+    ir:       Infrared,
+    events:   VecDeque<CartridgeEvent>,
+}
+
+impl HuC1 {
+    pub fn new() -> Self {
+        HuC1 { ir_mode: false, rom_bank: 1, ram_bank: 0, ir: Infrared::new(), events: VecDeque::new() }
+    }
+}
+
+impl Mapper for HuC1 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, 0, self.rom_bank as usize, addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => { self.ir_mode = val & 0x0F == 0x0E }
+            0x2000..=0x3FFF => {
+                self.rom_bank = val & 0x3F;
+                if self.rom_bank == 0 { self.rom_bank = 1; }
+            }
+            0x4000..=0x5FFF => { self.ram_bank = val & 0x03 }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if self.ir_mode { return self.ir.read(); }
+        if ram.is_empty() { return 0xFF; }
+        ram[ram_offset(ram, self.ram_bank as usize, addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if self.ir_mode {
+            if let Some(event) = self.ir.write(val) { self.events.push_back(event); }
+            return;
+        }
+        if ram.is_empty() { return; }
+        ram[ram_offset(ram, self.ram_bank as usize, addr)] = val;
+    }
+
+    // No enable register, RAM is only out of reach while the IR port is mapped
+    fn ram_enabled(&self) -> bool { !self.ir_mode }
+
+    fn infrared(&mut self) -> Option<&mut Infrared> { Some(&mut self.ir) }
+
+    fn poll_event(&mut self) -> Option<CartridgeEvent> { self.events.pop_front() }
+}
@@ -0,0 +1,154 @@
+use std::collections::VecDeque;
+
+use crate::cartridge::CartridgeEvent;
+use crate::mbc::{Mapper, Infrared, ram_offset, read_banked, rtc::{Rtc, ClockSource}};
+
+// Values written to 0x0000-0x1FFF select what 0xA000-0xBFFF talks to
+const MODE_RAM_READ:    u8 = 0x00;
+const MODE_RAM:         u8 = 0x0A;
+const MODE_RTC_COMMAND: u8 = 0x0B;
+const MODE_RTC_RESULT:  u8 = 0x0C;
+const MODE_RTC_READY:   u8 = 0x0D;
+const MODE_IR:          u8 = 0x0E;
+
+// RTC commands, upper nibble of a write in MODE_RTC_COMMAND
+const CMD_READ:         u8 = 0x1;
+const CMD_WRITE:        u8 = 0x3;
+const CMD_ADDR_LOW:     u8 = 0x4;
+const CMD_ADDR_HIGH:    u8 = 0x5;
+const CMD_EXTENDED:     u8 = 0x6;
+
+// Extended command arguments
+const EXT_LOAD_TIME:    u8 = 0x0;
+const EXT_STORE_TIME:   u8 = 0x1;
+const EXT_STATUS:       u8 = 0x2;
+
+// The game reads and writes the clock through a small nibble memory; minutes-of-day live in
+// nibbles 0x00-0x02 and the day count in 0x03-0x05
+const TIME_MINUTES:     usize = 0x00;
+const TIME_DAYS:        usize = 0x03;
+const DAY_LIMIT:        u64   = 0x1000;
+
+pub(crate) struct HuC3 {
+    mode:      u8,
+    rom_bank:  u8,
+    ram_bank:  u8,
+
+    rtc:       Rtc,
+    nibbles:   [u8; 0x100],
+    address:   u8,
+    command:   u8,
+    result:    u8,
+
+    ir:        Infrared,
+    events:    VecDeque<CartridgeEvent>,
+}
+
+impl HuC3 {
+    pub fn new() -> Self {
+        HuC3 {
+            mode: MODE_RAM_READ, rom_bank: 1, ram_bank: 0,
+            rtc: Rtc::with_day_limit(ClockSource::Host, DAY_LIMIT),
+            nibbles: [0; 0x100], address: 0, command: 0, result: 0,
+            ir: Infrared::new(), events: VecDeque::new(),
+        }
+    }
+
+    fn run_command(&mut self, val: u8) {
+        let arg = val & 0x0F;
+        self.command = (val >> 4) & 0x07;
+
+        match self.command {
+            CMD_READ => {
+                self.result  = self.nibbles[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            CMD_WRITE => {
+                self.nibbles[self.address as usize] = arg;
+                self.address = self.address.wrapping_add(1);
+            }
+            CMD_ADDR_LOW  => { self.address = (self.address & 0xF0) | arg }
+            CMD_ADDR_HIGH => { self.address = (self.address & 0x0F) | arg << 4 }
+            CMD_EXTENDED  => match arg {
+                EXT_LOAD_TIME  => self.load_time(),
+                EXT_STORE_TIME => self.store_time(),
+                EXT_STATUS     => { self.result = 0x1; }
+                _ => {} // Tone generator and alarm, not emulated
+            },
+            _ => {}
+        }
+    }
+
+    // Copy the running clock into the nibble memory
+    fn load_time(&mut self) {
+        self.rtc.sync();
+        let minutes = self.rtc.hours as u16 * 60 + self.rtc.minutes as u16;
+        let days    = self.rtc.days;
+
+        for i in 0..3 {
+            self.nibbles[TIME_MINUTES + i] = (minutes >> (i * 4)) as u8 & 0x0F;
+            self.nibbles[TIME_DAYS + i]    = (days    >> (i * 4)) as u8 & 0x0F;
+        }
+    }
+
+    // Set the running clock from the nibble memory
+    fn store_time(&mut self) {
+        let mut minutes = 0u16;
+        let mut days    = 0u16;
+        for i in 0..3 {
+            minutes |= (self.nibbles[TIME_MINUTES + i] as u16) << (i * 4);
+            days    |= (self.nibbles[TIME_DAYS + i]    as u16) << (i * 4);
+        }
+
+        self.rtc.sync();
+        self.rtc.seconds = 0;
+        self.rtc.minutes = (minutes % 60) as u8;
+        self.rtc.hours   = ((minutes / 60) % 24) as u8;
+        self.rtc.days    = days;
+    }
+}
+
+impl Mapper for HuC3 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, 0, self.rom_bank as usize, addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => { self.mode = val & 0x0F }
+            0x2000..=0x3FFF => { self.rom_bank = val & 0x7F }
+            0x4000..=0x5FFF => { self.ram_bank = val & 0x03 }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        match self.mode {
+            MODE_RAM_READ | MODE_RAM if !ram.is_empty() => ram[ram_offset(ram, self.ram_bank as usize, addr)],
+            MODE_RTC_RESULT => 0x80 | self.command << 4 | self.result,
+            MODE_RTC_READY  => 0xFF,    // Commands complete instantly, the semaphore always reads ready
+            MODE_IR         => self.ir.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        match self.mode {
+            MODE_RAM if !ram.is_empty() => { ram[ram_offset(ram, self.ram_bank as usize, addr)] = val; }
+            MODE_RTC_COMMAND => { self.run_command(val); }
+            MODE_IR => { if let Some(event) = self.ir.write(val) { self.events.push_back(event); } }
+            _ => {}
+        }
+    }
+
+    // Only 0x0A makes RAM writable, the other modes are read-only or talk to the clock and IR port
+    fn ram_enabled(&self) -> bool { self.mode == MODE_RAM }
+
+    fn step(&mut self, cycles: u16) { self.rtc.step(cycles); }
+
+    fn rtc(&mut self) -> Option<&mut Rtc> { Some(&mut self.rtc) }
+
+    fn infrared(&mut self) -> Option<&mut Infrared> { Some(&mut self.ir) }
+
+    fn poll_event(&mut self) -> Option<CartridgeEvent> { self.events.pop_front() }
+}
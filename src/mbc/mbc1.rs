@@ -0,0 +1,56 @@
+use crate::mbc::{Mapper, ram_offset, read_banked};
+
+pub(crate) struct MBC1 {
+    ram_enabled: bool,
+    bank1:       u8,    // 5-bit ROM bank register (0x2000-0x3FFF)
+    bank2:       u8,    // 2-bit RAM bank / upper ROM bank register (0x4000-0x5FFF)
+    mode:        bool,  // Banking mode select (0x6000-0x7FFF)
+}
+
+impl MBC1 {
+    pub fn new() -> Self {
+        MBC1 { ram_enabled: false, bank1: 1, bank2: 0, mode: false }
+    }
+
+    fn low_bank(&self) -> usize {
+        // In mode 1 the upper bits also apply to 0x0000-0x3FFF, which is how 1 MiB+ carts reach
+        // banks 0x20/0x40/0x60
+        if self.mode { (self.bank2 as usize) << 5 } else { 0 }
+    }
+
+    fn high_bank(&self) -> usize { (self.bank2 as usize) << 5 | self.bank1 as usize }
+
+    fn ram_bank(&self) -> usize { if self.mode { self.bank2 as usize } else { 0 } }
+}
+
+impl Mapper for MBC1 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, self.low_bank(), self.high_bank(), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => { self.ram_enabled = val & 0x0F == 0x0A }
+            0x2000..=0x3FFF => {
+                // Bank 0 can't be selected here; the check happens on the full 5 bits, so 0x20 maps to 0x21
+                self.bank1 = val & 0x1F;
+                if self.bank1 == 0 { self.bank1 = 1; }
+            }
+            0x4000..=0x5FFF => { self.bank2 = val & 0x03 }
+            0x6000..=0x7FFF => { self.mode = val & 0x01 != 0 }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() { return 0xFF; }
+        ram[ram_offset(ram, self.ram_bank(), addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !self.ram_enabled || ram.is_empty() { return; }
+        ram[ram_offset(ram, self.ram_bank(), addr)] = val;
+    }
+
+    fn ram_enabled(&self) -> bool { self.ram_enabled }
+}
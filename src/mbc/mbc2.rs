@@ -0,0 +1,46 @@
+use crate::mbc::{Mapper, read_banked};
+
+const RAM_CELLS: usize = 0x200;
+
+pub(crate) struct MBC2 {
+    ram_enabled: bool,
+    rom_bank:    u8,
+}
+
+impl MBC2 {
+    pub fn new() -> Self {
+        MBC2 { ram_enabled: false, rom_bank: 1 }
+    }
+}
+
+impl Mapper for MBC2 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, 0, self.rom_bank as usize, addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        // Both registers share 0x0000-0x3FFF, address bit 8 picks which one is written
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => { self.ram_enabled = val & 0x0F == 0x0A }
+            0x0000..=0x3FFF => {
+                self.rom_bank = val & 0x0F;
+                if self.rom_bank == 0 { self.rom_bank = 1; }
+            }
+            _ => {}
+        }
+    }
+
+    // The built-in RAM is 512 half-bytes echoed across the whole 0xA000-0xBFFF window; the upper
+    // nibble isn't connected and reads back as ones
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() { return 0xFF; }
+        0xF0 | ram[addr as usize % RAM_CELLS] & 0x0F
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !self.ram_enabled || ram.is_empty() { return; }
+        ram[addr as usize % RAM_CELLS] = val & 0x0F;
+    }
+
+    fn ram_enabled(&self) -> bool { self.ram_enabled }
+}
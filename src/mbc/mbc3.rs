@@ -0,0 +1,73 @@
+use crate::mbc::{Mapper, ram_offset, read_banked, rtc::{self, Rtc, ClockSource}};
+
+pub(crate) struct MBC3 {
+    ram_enabled:  bool,
+    rom_bank:     u8,
+    select:       u8,           // 0x00-0x03 picks a RAM bank, 0x08-0x0C an RTC register
+    latch_armed:  bool,
+    rtc:          Option<Rtc>,
+}
+
+impl MBC3 {
+    pub fn new(has_timer: bool) -> Self {
+        MBC3 {
+            ram_enabled: false, rom_bank: 1, select: 0, latch_armed: false,
+            rtc: if has_timer { Some(Rtc::new(ClockSource::Host)) } else { None },
+        }
+    }
+
+    fn rtc_selected(&self) -> bool { (rtc::RTC_S..=rtc::RTC_DH).contains(&self.select) }
+}
+
+impl Mapper for MBC3 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, 0, self.rom_bank as usize, addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => { self.ram_enabled = val & 0x0F == 0x0A }
+            0x2000..=0x3FFF => {
+                self.rom_bank = val & 0x7F;
+                if self.rom_bank == 0 { self.rom_bank = 1; }
+            }
+            0x4000..=0x5FFF => { self.select = val }
+            0x6000..=0x7FFF => {
+                // Writing 0x00 then 0x01 copies the running clock into the readable registers
+                if self.latch_armed && val == 0x01 {
+                    if let Some(rtc) = self.rtc.as_mut() { rtc.latch(); }
+                }
+                self.latch_armed = val == 0x00;
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled { return 0xFF; }
+
+        match self.select {
+            0x00..=0x03 if !ram.is_empty() => ram[ram_offset(ram, self.select as usize, addr)],
+            _ if self.rtc_selected() => self.rtc.as_ref().map_or(0xFF, |rtc| rtc.read(self.select)),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !self.ram_enabled { return; }
+
+        match self.select {
+            0x00..=0x03 if !ram.is_empty() => { ram[ram_offset(ram, self.select as usize, addr)] = val; }
+            _ if self.rtc_selected() => { if let Some(rtc) = self.rtc.as_mut() { rtc.write(self.select, val); } }
+            _ => {}
+        }
+    }
+
+    fn ram_enabled(&self) -> bool { self.ram_enabled }
+
+    fn step(&mut self, cycles: u16) {
+        if let Some(rtc) = self.rtc.as_mut() { rtc.step(cycles); }
+    }
+
+    fn rtc(&mut self) -> Option<&mut Rtc> { self.rtc.as_mut() }
+}
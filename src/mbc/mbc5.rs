@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+
+use crate::cartridge::CartridgeEvent;
+use crate::mbc::{Mapper, ram_offset, read_banked};
+
+pub(crate) struct MBC5 {
+    ram_enabled: bool,
+    rom_bank:    u16,   // 9-bit ROM bank, bank 0 is selectable here unlike MBC1/3
+    ram_bank:    u8,
+    rumble:      Option<bool>,
+    events:      VecDeque<CartridgeEvent>,
+}
+
+impl MBC5 {
+    pub fn new(has_rumble: bool) -> Self {
+        MBC5 {
+            ram_enabled: false, rom_bank: 1, ram_bank: 0,
+            rumble: if has_rumble { Some(false) } else { None },
+            events: VecDeque::new(),
+        }
+    }
+}
+
+impl Mapper for MBC5 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, 0, self.rom_bank as usize, addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => { self.ram_enabled = val & 0x0F == 0x0A }
+            0x2000..=0x2FFF => { self.rom_bank = (self.rom_bank & 0x100) | val as u16 }
+            0x3000..=0x3FFF => { self.rom_bank = (self.rom_bank & 0x0FF) | ((val & 0x01) as u16) << 8 }
+            0x4000..=0x5FFF => {
+                // On rumble carts bit 3 drives the motor instead of a RAM address line
+                match self.rumble {
+                    Some(state) => {
+                        let motor = val & 0x08 != 0;
+                        if motor != state {
+                            self.rumble = Some(motor);
+                            self.events.push_back(CartridgeEvent::Rumble(motor));
+                        }
+                        self.ram_bank = val & 0x07;
+                    }
+                    None => { self.ram_bank = val & 0x0F }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() { return 0xFF; }
+        ram[ram_offset(ram, self.ram_bank as usize, addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !self.ram_enabled || ram.is_empty() { return; }
+        ram[ram_offset(ram, self.ram_bank as usize, addr)] = val;
+    }
+
+    fn ram_enabled(&self) -> bool { self.ram_enabled }
+
+    fn poll_event(&mut self) -> Option<CartridgeEvent> { self.events.pop_front() }
+}
@@ -0,0 +1,232 @@
+use crate::mbc::{Mapper, read_banked};
+
+// Accelerometer output at rest and per 1g of tilt
+const ACCEL_CENTER:     f32 = 0x81D0 as f32;
+const ACCEL_SCALE:      f32 = 0x70 as f32;
+const ACCEL_UNLATCHED:  u16 = 0x8000;
+
+// 93LC56 in x16 organisation: 128 words, 2 opcode bits + 8 address bits after the start bit
+pub(crate) const EEPROM_SIZE: usize = 0x100;
+const EEPROM_WORDS:     usize = EEPROM_SIZE / 2;
+const COMMAND_BITS:     u8    = 10;
+
+const EEPROM_CS:        u8 = 0x80;
+const EEPROM_CLK:       u8 = 0x40;
+const EEPROM_DI:        u8 = 0x02;
+const EEPROM_DO:        u8 = 0x01;
+
+// Tilt in g along each axis, fed by the frontend
+pub struct Accelerometer {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EepromState { Idle, Command, Read(usize), Write(usize), WriteAll, Done }
+
+struct Eeprom {
+    state:         EepromState,
+    write_enabled: bool,
+    cs:            bool,
+    clk:           bool,
+    data_out:      bool,
+    shift:         u16,
+    bits:          u8,
+}
+
+impl Eeprom {
+    fn new() -> Self {
+        Eeprom {
+            state: EepromState::Idle, write_enabled: false,
+            cs: false, clk: false, data_out: true, shift: 0, bits: 0,
+        }
+    }
+
+    fn read_word(ram: &[u8], addr: usize) -> u16 {
+        let addr = (addr % EEPROM_WORDS) * 2;
+        ram[addr] as u16 | (ram[addr + 1] as u16) << 8
+    }
+
+    fn write_word(ram: &mut [u8], addr: usize, val: u16) {
+        let addr = (addr % EEPROM_WORDS) * 2;
+        ram[addr]     = val as u8;
+        ram[addr + 1] = (val >> 8) as u8;
+    }
+
+    fn read(&self) -> u8 {
+        (if self.cs  { EEPROM_CS  } else { 0 }) |
+        (if self.clk { EEPROM_CLK } else { 0 }) |
+        (if self.data_out { EEPROM_DO } else { 0 })
+    }
+
+    fn write(&mut self, ram: &mut [u8], val: u8) {
+        use EepromState::*;
+
+        let cs     = val & EEPROM_CS  != 0;
+        let clk    = val & EEPROM_CLK != 0;
+        let di     = (val & EEPROM_DI != 0) as u16;
+        let rising = clk && !self.clk;
+
+        self.cs  = cs;
+        self.clk = clk;
+
+        // Dropping chip select aborts whatever was going on
+        if !cs {
+            self.state    = Idle;
+            self.data_out = true;
+            return;
+        }
+        if !rising { return; }
+
+        match self.state {
+            Idle => {
+                // Wait for the start bit
+                if di == 1 { self.state = Command; self.shift = 0; self.bits = 0; }
+            }
+            Command => {
+                self.shift = self.shift << 1 | di;
+                self.bits += 1;
+                if self.bits == COMMAND_BITS { self.decode(ram); }
+            }
+            Read(addr) => {
+                // Sequential read: after the last bit of a word, continue with the next one
+                self.data_out = self.shift & 0x8000 != 0;
+                self.shift <<= 1;
+                self.bits += 1;
+                if self.bits == 16 {
+                    let next = addr + 1;
+                    self.state = Read(next);
+                    self.shift = Eeprom::read_word(ram, next);
+                    self.bits  = 0;
+                }
+            }
+            Write(_) | WriteAll => {
+                self.shift = self.shift << 1 | di;
+                self.bits += 1;
+                if self.bits < 16 { return; }
+
+                if self.write_enabled {
+                    match self.state {
+                        Write(addr) => Eeprom::write_word(ram, addr, self.shift),
+                        _ => (0..EEPROM_WORDS).for_each(|addr| Eeprom::write_word(ram, addr, self.shift)),
+                    }
+                }
+                self.state    = Done;
+                self.data_out = true;
+            }
+            Done => {}
+        }
+    }
+
+    fn decode(&mut self, ram: &mut [u8]) {
+        use EepromState::*;
+
+        let opcode = (self.shift >> 8) & 0x03;
+        let addr   = (self.shift & 0x7F) as usize;
+
+        self.bits  = 0;
+        self.state = Done;
+
+        match opcode {
+            0b10 => {
+                // A dummy zero goes out before the data
+                self.state    = Read(addr);
+                self.shift    = Eeprom::read_word(ram, addr);
+                self.data_out = false;
+            }
+            0b01 => { self.state = Write(addr); self.shift = 0; }
+            0b11 => { if self.write_enabled { Eeprom::write_word(ram, addr, 0xFFFF); } }
+            _ => match (self.shift >> 6) & 0x03 {
+                0b11 => { self.write_enabled = true; }
+                0b00 => { self.write_enabled = false; }
+                0b10 => { if self.write_enabled { ram.iter_mut().for_each(|b| *b = 0xFF); } }
+                _    => { self.state = WriteAll; self.shift = 0; }
+            },
+        }
+    }
+}
+
+pub(crate) struct MBC7 {
+    ram_enabled:  bool,     // Needs 0x0A at 0x0000-0x1FFF
+    ram_enabled2: bool,     // and 0x40 at 0x4000-0x5FFF
+    rom_bank:     u8,
+
+    accel:        Accelerometer,
+    latch_ready:  bool,
+    x_latch:      u16,
+    y_latch:      u16,
+
+    eeprom:       Eeprom,
+}
+
+impl MBC7 {
+    pub fn new() -> Self {
+        MBC7 {
+            ram_enabled: false, ram_enabled2: false, rom_bank: 1,
+            accel: Accelerometer { x: 0.0, y: 0.0 }, latch_ready: false,
+            x_latch: ACCEL_UNLATCHED, y_latch: ACCEL_UNLATCHED,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn registers_enabled(&self) -> bool { self.ram_enabled && self.ram_enabled2 }
+
+    fn axis(g: f32) -> u16 { (ACCEL_CENTER + ACCEL_SCALE * g).clamp(0.0, u16::MAX as f32) as u16 }
+}
+
+impl Mapper for MBC7 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, 0, self.rom_bank as usize, addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = val & 0x0F == 0x0A;
+                if !self.ram_enabled { self.ram_enabled2 = false; }
+            }
+            0x2000..=0x3FFF => { self.rom_bank = val & 0x7F }
+            0x4000..=0x5FFF if self.ram_enabled => { self.ram_enabled2 = val == 0x40 }
+            _ => {}
+        }
+    }
+
+    // Registers repeat every 0x100 bytes over 0xA000-0xAFFF, address bits 4-7 pick one
+    fn read_ram(&self, _ram: &[u8], addr: u16) -> u8 {
+        if !self.registers_enabled() || addr >= 0xB000 { return 0xFF; }
+
+        match (addr >> 4) & 0x0F {
+            0x2 => self.x_latch as u8,
+            0x3 => (self.x_latch >> 8) as u8,
+            0x4 => self.y_latch as u8,
+            0x5 => (self.y_latch >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _   => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !self.registers_enabled() || addr >= 0xB000 { return; }
+
+        match (addr >> 4) & 0x0F {
+            // Erase, then latch: the game has to clear the old sample before taking a new one
+            0x0 if val == 0x55 => {
+                self.latch_ready = true;
+                self.x_latch = ACCEL_UNLATCHED;
+                self.y_latch = ACCEL_UNLATCHED;
+            }
+            0x1 if val == 0xAA && self.latch_ready => {
+                self.latch_ready = false;
+                self.x_latch = MBC7::axis(self.accel.x);
+                self.y_latch = MBC7::axis(self.accel.y);
+            }
+            0x8 if ram.len() >= EEPROM_SIZE => { self.eeprom.write(ram, val) }
+            _ => {}
+        }
+    }
+
+    fn ram_enabled(&self) -> bool { self.registers_enabled() }
+
+    fn accelerometer(&mut self) -> Option<&mut Accelerometer> { Some(&mut self.accel) }
+}
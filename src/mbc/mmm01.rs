@@ -0,0 +1,98 @@
+use crate::mbc::{Mapper, ram_offset, read_banked};
+
+// Multicart controller. Until the menu locks the configuration the cart shows the last 32 KiB of
+// ROM, and the outer bank bits can only be set while unlocked.
+pub(crate) struct MMM01 {
+    locked:        bool,
+    ram_enabled:   bool,
+
+    rom_low:       u8,      // 5 bits, behaves like the MBC1 bank register
+    rom_mid:       u8,      // 2 bits, only writable while unlocked
+    rom_high:      u8,      // 2 bits, only writable while unlocked
+    rom_mask:      u8,      // Bits of rom_low frozen at lock time
+    ram_low:       u8,
+    ram_high:      u8,      // Only writable while unlocked
+
+    mode:          bool,
+    mode_locked:   bool,
+}
+
+impl MMM01 {
+    pub fn new() -> Self {
+        MMM01 {
+            locked: false, ram_enabled: false,
+            rom_low: 0, rom_mid: 0, rom_high: 0, rom_mask: 0, ram_low: 0, ram_high: 0,
+            mode: false, mode_locked: false,
+        }
+    }
+
+    fn outer_bank(&self) -> usize {
+        (self.rom_high as usize) << 7 | (self.rom_mid as usize) << 5
+    }
+
+    fn low_bank(&self) -> usize {
+        if !self.locked { return 0x1FE; }
+        self.outer_bank() | (self.rom_low & self.rom_mask) as usize
+    }
+
+    fn high_bank(&self) -> usize {
+        if !self.locked { return 0x1FF; }
+
+        // Like MBC1, an all-zero (unmasked) bank register selects the next bank up
+        let free = self.rom_low & !self.rom_mask;
+        let free = if free == 0 { 1 } else { free };
+        self.outer_bank() | ((self.rom_low & self.rom_mask) | free) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        let low = if self.mode { self.ram_low } else { 0 };
+        (self.ram_high as usize) << 2 | low as usize
+    }
+}
+
+impl Mapper for MMM01 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        read_banked(rom, self.low_bank(), self.high_bank(), addr)
+    }
+
+    fn write_rom(&mut self, addr: u16, val: u8) {
+        match addr {
+            0x0000..=0x1FFF => {
+                self.ram_enabled = val & 0x0F == 0x0A;
+                if !self.locked && val & 0x40 != 0 { self.locked = true; }
+            }
+            0x2000..=0x3FFF => {
+                self.rom_low = (self.rom_low & self.rom_mask) | (val & 0x1F & !self.rom_mask);
+                if !self.locked {
+                    self.rom_low = val & 0x1F;
+                    self.rom_mid = (val >> 5) & 0x03;
+                }
+            }
+            0x4000..=0x5FFF => {
+                self.ram_low = val & 0x03;
+                if !self.locked {
+                    self.ram_high    = (val >> 2) & 0x03;
+                    self.rom_high    = (val >> 4) & 0x03;
+                    self.mode_locked = val & 0x40 != 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mode_locked { self.mode = val & 0x01 != 0; }
+                if !self.locked { self.rom_mask = (val & 0x3C) >> 1; }
+            }
+            _ => {}
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() { return 0xFF; }
+        ram[ram_offset(ram, self.ram_bank(), addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !self.ram_enabled || ram.is_empty() { return; }
+        ram[ram_offset(ram, self.ram_bank(), addr)] = val;
+    }
+
+    fn ram_enabled(&self) -> bool { self.ram_enabled }
+}
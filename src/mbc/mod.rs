@@ -0,0 +1,356 @@
+pub mod huc1;
+pub mod huc3;
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod mbc7;
+pub mod mmm01;
+pub mod rtc;
+
+use crate::cartridge::{CartridgeEvent, CartridgeHeader, CartridgeKind};
+use crate::mbc::{mbc7::Accelerometer, rtc::Rtc};
+
+pub(crate) const ROM_BANK_SIZE: usize = 0x4000;
+pub(crate) const RAM_BANK_SIZE: usize = 0x2000;
+
+const ROM_BANK_X:   u16 = 0x4000;
+const CRAM_START:   u16 = 0xA000;
+
+// Every cartridge controller sits between the bus and the ROM/RAM chips.
+// Control writes land in 0x0000-0x7FFF, external RAM lives at 0xA000-0xBFFF.
+pub(crate) trait Mapper {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8;
+    fn write_rom(&mut self, addr: u16, val: u8);
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8;
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8);
+
+    // Whether the game currently has cart RAM switched on; a drop to false is its way of saying
+    // it's done writing
+    fn ram_enabled(&self) -> bool { true }
+
+    // Advance anything on the cart that runs off the system clock
+    fn step(&mut self, _cycles: u16) {}
+
+    fn rtc(&mut self) -> Option<&mut Rtc> { None }
+
+    fn infrared(&mut self) -> Option<&mut Infrared> { None }
+
+    fn accelerometer(&mut self) -> Option<&mut Accelerometer> { None }
+
+    // Things the frontend may want to react to (rumble motor, ...)
+    fn poll_event(&mut self) -> Option<CartridgeEvent> { None }
+}
+
+pub(crate) fn create(header: &CartridgeHeader) -> Option<Box<dyn Mapper>> {
+    use CartridgeKind::*;
+
+    Some(match header.kind {
+        RomOnly | RomRam | RomRamBattery    => Box::new(NoMapper::new()),
+        Mbc1 | Mbc1Ram | Mbc1RamBattery     => Box::new(mbc1::MBC1::new()),
+        Mbc2 | Mbc2Battery                  => Box::new(mbc2::MBC2::new()),
+        Mmm01 | Mmm01Ram | Mmm01RamBattery  => Box::new(mmm01::MMM01::new()),
+        Mbc3 | Mbc3Ram | Mbc3RamBattery |
+        Mbc3TimerBattery | Mbc3TimerRamBattery => Box::new(mbc3::MBC3::new(header.kind.has_timer())),
+        Mbc5 | Mbc5Ram | Mbc5RamBattery |
+        Mbc5Rumble | Mbc5RumbleRam | Mbc5RumbleRamBattery => Box::new(mbc5::MBC5::new(header.kind.has_rumble())),
+
+        Mbc7SensorRumbleRamBattery          => Box::new(mbc7::MBC7::new()),
+        HuC1RamBattery                      => Box::new(huc1::HuC1::new()),
+        HuC3                                => Box::new(huc3::HuC3::new()),
+
+        _ => return None,
+    })
+}
+
+// MBC2 carries its own 512x4-bit RAM and MBC7 an EEPROM, neither is reported in the header
+pub(crate) fn ram_size(header: &CartridgeHeader) -> usize {
+    use CartridgeKind::*;
+
+    match header.kind {
+        Mbc2 | Mbc2Battery          => 0x200,
+        Mbc7SensorRumbleRamBattery  => mbc7::EEPROM_SIZE,
+        _ => header.ram_size,
+    }
+}
+
+// Translate a banked address into an offset, wrapping banks past the end of the chip like the
+// unconnected address lines on real carts do
+pub(crate) fn rom_offset(rom: &[u8], bank: usize, addr: u16) -> usize {
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    (bank % banks) * ROM_BANK_SIZE + (addr as usize % ROM_BANK_SIZE)
+}
+
+pub(crate) fn ram_offset(ram: &[u8], bank: usize, addr: u16) -> usize {
+    let banks = (ram.len() / RAM_BANK_SIZE).max(1);
+    ((bank % banks) * RAM_BANK_SIZE + (addr - CRAM_START) as usize) % ram.len()
+}
+
+pub(crate) fn read_banked(rom: &[u8], low_bank: usize, high_bank: usize, addr: u16) -> u8 {
+    let bank = if addr < ROM_BANK_X { low_bank } else { high_bank };
+    rom[rom_offset(rom, bank, addr)]
+}
+
+// Plain 32 KiB carts, optionally with up to 8 KiB of RAM wired straight to the bus
+pub(crate) struct NoMapper {}
+
+impl NoMapper {
+    pub fn new() -> Self { NoMapper {} }
+}
+
+impl Mapper for NoMapper {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 { read_banked(rom, 0, 1, addr) }
+    fn write_rom(&mut self, _addr: u16, _val: u8) {}
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if ram.is_empty() { 0xFF } else { ram[ram_offset(ram, 0, addr)] }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, val: u8) {
+        if !ram.is_empty() { ram[ram_offset(ram, 0, addr)] = val; }
+    }
+}
+
+// IR port found on HuC1/HuC3 carts. Reads see 0xC1 while light is being received.
+#[derive(Default)]
+pub struct Infrared {
+    pub led:   bool,
+    pub light: bool,
+}
+
+impl Infrared {
+    pub fn new() -> Self { Infrared { led: false, light: false } }
+
+    pub fn read(&self) -> u8 { if self.light { 0xC1 } else { 0xC0 } }
+
+    pub fn write(&mut self, val: u8) -> Option<CartridgeEvent> {
+        let led = val & 0x01 != 0;
+        if led == self.led { return None; }
+
+        self.led = led;
+        Some(CartridgeEvent::Infrared(led))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mbc::rtc::{ClockSource, RTC_S, RTC_M};
+
+    // Each bank starts with its own number, so reads show which bank is mapped where
+    fn banked_rom(banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; banks * ROM_BANK_SIZE];
+        for (bank, chunk) in rom.chunks_exact_mut(ROM_BANK_SIZE).enumerate() {
+            chunk[..2].copy_from_slice(&(bank as u16).to_le_bytes());
+        }
+        rom
+    }
+
+    // Banks mapped at 0x0000 and 0x4000
+    fn banks(mapper: &dyn Mapper, rom: &[u8]) -> (usize, usize) {
+        let bank = |addr| u16::from_le_bytes([mapper.read_rom(rom, addr), mapper.read_rom(rom, addr + 1)]) as usize;
+        (bank(0x0000), bank(0x4000))
+    }
+
+    #[test]
+    fn mbc1_banking() {
+        let (rom, mut ram) = (banked_rom(128), vec![0; 4 * RAM_BANK_SIZE]);
+        let mut mbc = mbc1::MBC1::new();
+        assert_eq!(banks(&mbc, &rom), (0, 1));
+
+        // Zero in the low 5 bits means bank 1, even for 0x20
+        mbc.write_rom(0x2000, 0x20);
+        assert_eq!(banks(&mbc, &rom), (0, 1));
+
+        mbc.write_rom(0x2000, 0x05);
+        mbc.write_rom(0x4000, 0x02);
+        assert_eq!(banks(&mbc, &rom), (0, 0x45));
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(banks(&mbc, &rom), (0x40, 0x45));
+
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0x12);
+        assert_eq!(ram[2 * RAM_BANK_SIZE], 0x12);
+
+        // Mode 0 pins RAM bank 0
+        mbc.write_rom(0x6000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x00);
+    }
+
+    #[test]
+    fn mbc3_banking() {
+        let (rom, mut ram) = (banked_rom(128), vec![0; 4 * RAM_BANK_SIZE]);
+        let mut mbc = mbc3::MBC3::new(false);
+
+        mbc.write_rom(0x2000, 0x7F);
+        assert_eq!(banks(&mbc, &rom), (0, 0x7F));
+        mbc.write_rom(0x2000, 0x00);
+        assert_eq!(banks(&mbc, &rom), (0, 1));
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x03);
+        mbc.write_ram(&mut ram, 0xA010, 0x34);
+        assert_eq!(ram[3 * RAM_BANK_SIZE + 0x10], 0x34);
+
+        // No clock on this cart
+        mbc.write_rom(0x4000, RTC_S);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+
+    #[test]
+    fn mbc3_rtc_reads_latched_values() {
+        let mut ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = mbc3::MBC3::new(true);
+        mbc.rtc().unwrap().set_source(ClockSource::Emulated);
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, RTC_S);
+        mbc.write_ram(&mut ram, 0xA000, 59);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0);
+
+        let latch = |mbc: &mut mbc3::MBC3| { mbc.write_rom(0x6000, 0x00); mbc.write_rom(0x6000, 0x01); };
+        latch(&mut mbc);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 59);
+
+        // One emulated second later the latched copy hasn't moved
+        for _ in 0..64 { mbc.step(0x8000); mbc.step(0x8000); }
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 59);
+
+        // Writing 0x01 alone doesn't latch
+        mbc.write_rom(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 59);
+
+        latch(&mut mbc);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0);
+        mbc.write_rom(0x4000, RTC_M);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 1);
+    }
+
+    #[test]
+    fn mbc5_banking_and_rumble() {
+        let (rom, mut ram) = (banked_rom(512), vec![0; 16 * RAM_BANK_SIZE]);
+        let mut mbc = mbc5::MBC5::new(false);
+
+        mbc.write_rom(0x2000, 0xFF);
+        mbc.write_rom(0x3000, 0x01);
+        assert_eq!(banks(&mbc, &rom), (0, 0x1FF));
+
+        // Bank 0 is fair game in the switchable area
+        mbc.write_rom(0x2000, 0x00);
+        mbc.write_rom(0x3000, 0x00);
+        assert_eq!(banks(&mbc, &rom), (0, 0));
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x0F);
+        mbc.write_ram(&mut ram, 0xA000, 0x56);
+        assert_eq!(ram[15 * RAM_BANK_SIZE], 0x56);
+
+        // Rumble carts lose RAM bank bit 3 to the motor
+        let mut mbc = mbc5::MBC5::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x09);
+        assert_eq!(mbc.poll_event(), Some(CartridgeEvent::Rumble(true)));
+        assert_eq!(mbc.poll_event(), None);
+        mbc.write_ram(&mut ram, 0xA000, 0x78);
+        assert_eq!(ram[RAM_BANK_SIZE], 0x78);
+    }
+
+    #[test]
+    fn mbc7_banking_and_accelerometer() {
+        let (rom, mut ram) = (banked_rom(128), vec![0; mbc7::EEPROM_SIZE]);
+        let mut mbc = mbc7::MBC7::new();
+
+        mbc.write_rom(0x2000, 0x10);
+        assert_eq!(banks(&mbc, &rom), (0, 0x10));
+
+        // Both enables are needed before the registers show up
+        mbc.write_rom(0x0000, 0x0A);
+        assert_eq!(mbc.read_ram(&ram, 0xA020), 0xFF);
+        mbc.write_rom(0x4000, 0x40);
+
+        mbc.accelerometer().unwrap().x = 1.0;
+        mbc.write_ram(&mut ram, 0xA000, 0x55);
+        mbc.write_ram(&mut ram, 0xA010, 0xAA);
+        assert_eq!(mbc.read_ram(&ram, 0xA020) as u16 | (mbc.read_ram(&ram, 0xA030) as u16) << 8, 0x81D0 + 0x70);
+        assert_eq!(mbc.read_ram(&ram, 0xA040) as u16 | (mbc.read_ram(&ram, 0xA050) as u16) << 8, 0x81D0);
+    }
+
+    #[test]
+    fn mbc2_banking_and_nibble_ram() {
+        let (rom, mut ram) = (banked_rom(16), vec![0; 0x200]);
+        let mut mbc = mbc2::MBC2::new();
+
+        // Address bit 8 picks the register
+        mbc.write_rom(0x2100, 0x03);
+        assert_eq!(banks(&mbc, &rom), (0, 3));
+        mbc.write_rom(0x0100, 0x00);
+        assert_eq!(banks(&mbc, &rom), (0, 1));
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA001, 0xAB);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0xFB);
+        assert_eq!(mbc.read_ram(&ram, 0xA201), 0xFB);
+    }
+
+    #[test]
+    fn mmm01_banking() {
+        let rom = banked_rom(512);
+        let mut mbc = mmm01::MMM01::new();
+
+        // Unlocked, the menu at the end of the ROM shows up
+        assert_eq!(banks(&mbc, &rom), (0x1FE, 0x1FF));
+
+        mbc.write_rom(0x2000, 0x01 << 5 | 0x02);
+        mbc.write_rom(0x4000, 0x01 << 4);
+        mbc.write_rom(0x0000, 0x40);
+        assert_eq!(banks(&mbc, &rom), (0xA0, 0xA2));
+
+        // Outer bank bits are frozen once locked
+        mbc.write_rom(0x4000, 0x03 << 4);
+        mbc.write_rom(0x2000, 0x03);
+        assert_eq!(banks(&mbc, &rom), (0xA0, 0xA3));
+    }
+
+    #[test]
+    fn huc1_banking_and_infrared() {
+        let (rom, mut ram) = (banked_rom(64), vec![0; 4 * RAM_BANK_SIZE]);
+        let mut mbc = huc1::HuC1::new();
+
+        mbc.write_rom(0x2000, 0x3F);
+        assert_eq!(banks(&mbc, &rom), (0, 0x3F));
+
+        mbc.write_rom(0x4000, 0x02);
+        mbc.write_ram(&mut ram, 0xA000, 0x9A);
+        assert_eq!(ram[2 * RAM_BANK_SIZE], 0x9A);
+
+        mbc.write_rom(0x0000, 0x0E);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xC0);
+        mbc.infrared().unwrap().light = true;
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xC1);
+        mbc.write_ram(&mut ram, 0xA000, 0x01);
+        assert_eq!(mbc.poll_event(), Some(CartridgeEvent::Infrared(true)));
+        assert_eq!(ram[2 * RAM_BANK_SIZE], 0x9A);
+    }
+
+    #[test]
+    fn huc3_banking_and_commands() {
+        let (rom, mut ram) = (banked_rom(128), vec![0; 4 * RAM_BANK_SIZE]);
+        let mut mbc = huc3::HuC3::new();
+
+        mbc.write_rom(0x2000, 0x05);
+        assert_eq!(banks(&mbc, &rom), (0, 5));
+
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x01);
+        mbc.write_ram(&mut ram, 0xA000, 0xBC);
+        assert_eq!(ram[RAM_BANK_SIZE], 0xBC);
+
+        // Extended status command, then read the result
+        mbc.write_rom(0x0000, 0x0B);
+        mbc.write_ram(&mut ram, 0xA000, 0x62);
+        mbc.write_rom(0x0000, 0x0C);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xE1);
+    }
+}
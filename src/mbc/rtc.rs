@@ -0,0 +1,268 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CYCLES_PER_SECOND: u64 = 4194304;
+const SECONDS_PER_DAY:   u64 = 86400;
+const MBC3_DAY_LIMIT:    u64 = 512;
+
+// Selecting an RTC register through the RAM bank register
+pub(crate) const RTC_S:  u8 = 0x08;
+pub(crate) const RTC_M:  u8 = 0x09;
+pub(crate) const RTC_H:  u8 = 0x0A;
+pub(crate) const RTC_DL: u8 = 0x0B;
+pub(crate) const RTC_DH: u8 = 0x0C;
+
+// Save trailer used by VBA/BGB/mGBA: ten little-endian u32 registers (live then latched) and a
+// 64-bit unix timestamp. Some older saves only store 32 bits of timestamp.
+pub(crate) const RTC_TRAILER_SIZE:       usize = 48;
+pub(crate) const RTC_TRAILER_SIZE_SHORT: usize = 44;
+
+const DH_DAY_MSB: u8 = 0x01;
+const DH_HALT:    u8 = 0x40;
+const DH_CARRY:   u8 = 0x80;
+
+// Where the clock gets its notion of elapsed time from. Host follows the wall clock like a real
+// cart does; Emulated counts CPU cycles so runs are reproducible.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClockSource { Host, Emulated }
+
+pub struct Rtc {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours:   u8,
+    pub days:    u16,   // 9-bit day counter on MBC3
+    pub halted:  bool,
+    pub carry:   bool,
+
+    latched:     [u8; 5],
+    source:      ClockSource,
+    last_sync:   SystemTime,
+    cycles:      u64,
+    day_limit:   u64,
+}
+
+impl Rtc {
+    pub fn new(source: ClockSource) -> Self { Rtc::with_day_limit(source, MBC3_DAY_LIMIT) }
+
+    pub fn with_day_limit(source: ClockSource, day_limit: u64) -> Self {
+        Rtc {
+            seconds: 0, minutes: 0, hours: 0, days: 0, halted: false, carry: false,
+            latched: [0; 5], source: source, last_sync: SystemTime::now(), cycles: 0, day_limit: day_limit,
+        }
+    }
+
+    pub fn source(&self) -> ClockSource { self.source }
+
+    pub fn set_source(&mut self, source: ClockSource) {
+        self.sync();
+        self.source    = source;
+        self.last_sync = SystemTime::now();
+        self.cycles    = 0;
+    }
+
+    // Only used by the emulated clock source, the host source ignores cycles entirely
+    pub fn step(&mut self, cycles: u16) {
+        if self.source != ClockSource::Emulated || self.halted { return; }
+
+        self.cycles += cycles as u64;
+        if self.cycles >= CYCLES_PER_SECOND {
+            let secs = self.cycles / CYCLES_PER_SECOND;
+            self.cycles %= CYCLES_PER_SECOND;
+            self.advance(secs);
+        }
+    }
+
+    // Catch up with the wall clock
+    pub fn sync(&mut self) {
+        if self.source != ClockSource::Host { return; }
+
+        let now = SystemTime::now();
+        let Ok(elapsed) = now.duration_since(self.last_sync) else { self.last_sync = now; return; };
+
+        // Keep the sub-second remainder around so frequent syncs don't lose time
+        let secs = elapsed.as_secs();
+        if secs == 0 { return; }
+        self.last_sync += std::time::Duration::from_secs(secs);
+
+        if !self.halted { self.advance(secs); }
+    }
+
+    pub fn advance(&mut self, mut secs: u64) {
+        // Out-of-range values written by the game count up to their bit limit and wrap without
+        // carrying, so tick those one at a time until everything is back in range
+        while secs > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick();
+            secs -= 1;
+        }
+        if secs == 0 { return; }
+
+        let total = self.seconds as u64 + self.minutes as u64 * 60 + self.hours as u64 * 3600 + secs;
+        let days  = self.days as u64 + total / SECONDS_PER_DAY;
+        let rest  = total % SECONDS_PER_DAY;
+
+        self.seconds = (rest % 60) as u8;
+        self.minutes = ((rest / 60) % 60) as u8;
+        self.hours   = (rest / 3600) as u8;
+
+        if days >= self.day_limit { self.carry = true; }
+        self.days = (days % self.day_limit) as u16;
+    }
+
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 { return; }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 { return; }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 { return; }
+        self.hours = 0;
+
+        self.days += 1;
+        if self.days as u64 == self.day_limit { self.days = 0; self.carry = true; }
+    }
+
+    pub fn latch(&mut self) {
+        self.sync();
+        self.latched = [self.seconds, self.minutes, self.hours, self.days as u8, self.read_dh()];
+    }
+
+    pub fn read(&self, reg: u8) -> u8 {
+        match reg {
+            RTC_S  => self.latched[0] & 0x3F,
+            RTC_M  => self.latched[1] & 0x3F,
+            RTC_H  => self.latched[2] & 0x1F,
+            RTC_DL => self.latched[3],
+            RTC_DH => self.latched[4] & (DH_DAY_MSB | DH_HALT | DH_CARRY),
+            _      => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, reg: u8, val: u8) {
+        self.sync();
+
+        match reg {
+            RTC_S  => { self.seconds = val & 0x3F; self.cycles = 0; self.last_sync = SystemTime::now(); }
+            RTC_M  => { self.minutes = val & 0x3F; }
+            RTC_H  => { self.hours   = val & 0x1F; }
+            RTC_DL => { self.days    = (self.days & 0x100) | val as u16; }
+            RTC_DH => {
+                self.days   = (self.days & 0xFF) | ((val & DH_DAY_MSB) as u16) << 8;
+                self.halted = val & DH_HALT  != 0;
+                self.carry  = val & DH_CARRY != 0;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn save_trailer(&mut self) -> [u8; RTC_TRAILER_SIZE] {
+        self.sync();
+
+        let live = [self.seconds, self.minutes, self.hours, self.days as u8, self.read_dh()];
+        let mut trailer = [0u8; RTC_TRAILER_SIZE];
+
+        for (i, reg) in live.iter().chain(self.latched.iter()).enumerate() {
+            trailer[i * 4..i * 4 + 4].copy_from_slice(&(*reg as u32).to_le_bytes());
+        }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        trailer[40..48].copy_from_slice(&timestamp.to_le_bytes());
+
+        trailer
+    }
+
+    pub fn load_trailer(&mut self, trailer: &[u8]) {
+        let reg = |i: usize| trailer[i * 4];
+
+        self.seconds = reg(0) & 0x3F;
+        self.minutes = reg(1) & 0x3F;
+        self.hours   = reg(2) & 0x1F;
+        self.days    = reg(3) as u16 | ((reg(4) & DH_DAY_MSB) as u16) << 8;
+        self.halted  = reg(4) & DH_HALT  != 0;
+        self.carry   = reg(4) & DH_CARRY != 0;
+        for i in 0..5 { self.latched[i] = reg(5 + i); }
+
+        let timestamp = if trailer.len() >= RTC_TRAILER_SIZE {
+            u64::from_le_bytes(trailer[40..48].try_into().unwrap())
+        } else {
+            u32::from_le_bytes(trailer[40..44].try_into().unwrap()) as u64
+        };
+
+        // Account for the time the emulator was closed, like a real cart would on its battery
+        self.last_sync = UNIX_EPOCH + std::time::Duration::from_secs(timestamp);
+        self.sync();
+    }
+
+    fn read_dh(&self) -> u8 {
+        (self.days >> 8) as u8 & DH_DAY_MSB |
+        if self.halted { DH_HALT  } else { 0 } |
+        if self.carry  { DH_CARRY } else { 0 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock(source: ClockSource) -> Rtc {
+        let mut rtc = Rtc::new(source);
+        rtc.write(RTC_S, 12);
+        rtc.write(RTC_M, 34);
+        rtc.write(RTC_H, 5);
+        rtc.write(RTC_DL, 0x23);
+        rtc.write(RTC_DH, DH_CARRY | DH_DAY_MSB);
+        rtc
+    }
+
+    #[test]
+    fn save_trailer_round_trips() {
+        let mut rtc = clock(ClockSource::Emulated);
+        rtc.latch();
+        rtc.write(RTC_S, 13);
+
+        let trailer = rtc.save_trailer();
+        assert_eq!(trailer[0..4], [13, 0, 0, 0]);
+        assert_eq!(trailer[20..24], [12, 0, 0, 0]);
+        assert_eq!(trailer[36..40], [DH_CARRY | DH_DAY_MSB, 0, 0, 0]);
+
+        for trailer in [&trailer[..], &trailer[..RTC_TRAILER_SIZE_SHORT]] {
+            let mut loaded = Rtc::new(ClockSource::Emulated);
+            loaded.load_trailer(trailer);
+
+            assert_eq!((loaded.seconds, loaded.minutes, loaded.hours, loaded.days), (13, 34, 5, 0x123));
+            assert!(loaded.carry && !loaded.halted);
+            assert_eq!(loaded.read(RTC_S), 12);
+            assert_eq!(loaded.read(RTC_DH), DH_CARRY | DH_DAY_MSB);
+        }
+    }
+
+    #[test]
+    fn loading_catches_up_with_time_spent_closed() {
+        let mut trailer = clock(ClockSource::Emulated).save_trailer();
+        let timestamp = u64::from_le_bytes(trailer[40..48].try_into().unwrap()) - 3600;
+        trailer[40..48].copy_from_slice(&timestamp.to_le_bytes());
+
+        let mut rtc = Rtc::new(ClockSource::Host);
+        rtc.load_trailer(&trailer);
+        assert_eq!((rtc.minutes, rtc.hours), (34, 6));
+
+        // A halted clock stays put
+        trailer[16] |= DH_HALT;
+        let mut rtc = Rtc::new(ClockSource::Host);
+        rtc.load_trailer(&trailer);
+        assert_eq!((rtc.seconds, rtc.minutes, rtc.hours), (12, 34, 5));
+    }
+
+    #[test]
+    fn day_counter_overflow_sets_carry() {
+        let mut rtc = Rtc::new(ClockSource::Emulated);
+        rtc.write(RTC_DL, 0xFF);
+        rtc.write(RTC_DH, DH_DAY_MSB);
+        rtc.advance(SECONDS_PER_DAY);
+
+        assert_eq!(rtc.days, 0);
+        assert!(rtc.carry);
+    }
+}
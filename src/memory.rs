@@ -1,53 +1,285 @@
-use crate::emulator::ROM;
-
-const BOOT_ROM: [u8; 0x71] = [
-    0x31, 0xFE, 0xFF, 0xAF, 0x21, 0x4C, 0x01, 0xCD, 0x87, 0x00, 0x31, 0xFE, 0xFF, 0x3E, 0x20, 0xE0,
-    0x50, 0x21, 0x00, 0x99, 0x0E, 0x0C, 0x3E, 0x30, 0xE0, 0x40, 0x11, 0xA8, 0x00, 0x1A, 0xCD, 0x95,
-    0x00, 0xCD, 0x96, 0x00, 0x13, 0x7D, 0x21, 0x01, 0x99, 0xCD, 0x86, 0x00, 0xAF, 0xC3, 0x52, 0x01,
-    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
-    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
-    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
-    0x3E, 0x19, 0xEA, 0x10, 0x00, 0x1A, 0xCD, 0x86, 0x00, 0xC9, 0x3E, 0x01, 0xE0, 0x50, 0xC3, 0x00,
-    0x01
-    ];
+use crate::boot::BootRom;
+use crate::cartridge::{ROM, CgbSupport};
+use crate::cheats::Cheats;
+use crate::hdma::{Hdma, HdmaMode, HDMA1, HDMA5, BLOCK_SIZE};
+use crate::model::Model;
+use crate::oam_dma::{OamDma, OAM_DMA};
+use crate::sgb::Sgb;
 
 // Memory mapping 
-const ROM_START:    u16 = 0x0000; const VROM_START:   u16 = 0x4000; const VRAM_START:   u16 = 0x8000; const CRAM_START:   u16 = 0xA000;
+const ROM_START:    u16 = 0x0000; const VRAM_START:   u16 = 0x8000; const CRAM_START:   u16 = 0xA000;
 const WRAM_START:   u16 = 0xC000; const ERAM_START:   u16 = 0xE000; const OAM_START:    u16 = 0xFE00; const UNUSED:       u16 = 0xFEA0;
 const IORG_START:   u16 = 0xFF00; const HRAM_START:   u16 = 0xFF80;
 
-const ROM_END:  u16 = VROM_START - 1; const VROM_END: u16 = VRAM_START - 1; const VRAM_END: u16 = CRAM_START - 1;
+const VROM_END: u16 = VRAM_START - 1; const VRAM_END: u16 = CRAM_START - 1;
 const CRAM_END: u16 = WRAM_START - 1; const WRAM_END: u16 = ERAM_START - 1; const ERAM_END: u16 = OAM_START - 1;
 const OAM_END:  u16 = UNUSED - 1;     const UNUSED_D: u16 = IORG_START - 1; const IORG_END: u16 = HRAM_START - 1;
-const HRAM_END: u16 = 0xFFFE;
 
 // Input/output registers
-const TIMER_START:  u16 = 0xFF04; 
-const TIMER_END:    u16 = 0xFF07;
+const JOYP:         u16 = 0xFF00;
+
+const BOOT_OFF:     u16 = 0xFF50;
+const STAT:         u16 = 0xFF41;
+const NR52:         u16 = 0xFF26;
+const NR10:         u16 = 0xFF10;
+const NR51:         u16 = 0xFF25;
+const KEY1:         u16 = 0xFF4D;
+const VBK:          u16 = 0xFF4F;
+const SVBK:         u16 = 0xFF70;
+const BCPS:         u16 = 0xFF68;
+const BCPD:         u16 = 0xFF69;
+const OCPS:         u16 = 0xFF6A;
+const OCPD:         u16 = 0xFF6B;
+const LCDC:         u16 = 0xFF40;
+
+const VRAM_BANK:  usize = 0x2000;
+const WRAM_BANK:  usize = 0x1000;
+const WRAM_SWAP:    u16 = WRAM_START + WRAM_BANK as u16;   // 0xD000, start of the switchable bank
+
+// Registers that only exist on CGB hardware
+const CGB_IO: [u16; 16] = [
+    0xFF4D, 0xFF4F, 0xFF51, 0xFF52, 0xFF53, 0xFF54, 0xFF55, 0xFF56,
+    0xFF68, 0xFF69, 0xFF6A, 0xFF6B, 0xFF6C, 0xFF70, 0xFF76, 0xFF77,
+];
+
+// DAC control and trigger register of each sound channel
+const SOUND_CHANNELS: [(u16, u16); 4] = [(0xFF12, 0xFF14), (0xFF17, 0xFF19), (0xFF1A, 0xFF1E), (0xFF21, 0xFF23)];
 
 const WVRAM_START:  u16 = 0xFF30;
 const WVRAM_END:    u16 = 0xFF3F;
 
 pub(crate) struct MemoryBus {
-    pub memory:    [u8; 0xFFFF],
+    pub memory:    [u8; 0x10000],       // OAM, I/O registers, HRAM and IE
+    pub vram:      [[u8; VRAM_BANK]; 2],
+    pub wram:      [[u8; WRAM_BANK]; 8],
+    pub bg_palettes:  [u8; 64],         // CGB palette RAM: 8 palettes of 4 little-endian RGB555 colors
+    pub obj_palettes: [u8; 64],
     pub pc:        u16,
     pub sp:        u16,
     pub ime:      bool,
     pub inf:        u8,
-        rom:       ROM,
+    pub rom:       ROM,
+    pub cheats: Cheats,
+    pub joypad: [u8; 4],                // Pressed buttons per joypad, see IPU::update_byte
+    pub channels_on: u8,                // NR52 bits 0-3, which sound channels are playing
+        boot:      Option<BootRom>,     // Overlaid on the cartridge until 0xFF50 is written
+
+    pub model:     Model,
+    pub cgb_mode:  bool,                // CGB hardware running a CGB-aware cart
+    pub oam_scan_row: u8,               // OAM row the PPU is reading during mode 2
+    pub double_speed: bool,             // CGB CPU running at 8 MHz
+    pub hdma:      Hdma,
+    pub dma_stall: u16,                 // T-cycles the CPU owes to DMA transfers
+    pub oam_dma:   OamDma,
+        dma_byte:  u8,                  // Last byte OAM DMA moved; what the CPU sees off HRAM meanwhile
+    pub sgb:       Option<Sgb>,         // SGB-aware cart running on a Super Game Boy
 }
 
 impl MemoryBus {
     pub fn new(rom: ROM) -> Self {
-        let mut memory: [u8; 0xFFFF] = [0; 0xFFFF];
-        memory[..0x71].copy_from_slice(&BOOT_ROM);
+        let memory: [u8; 0x10000] = [0; 0x10000];
+
+        let model = Model::for_header(&rom.header);
+
+        let mut bus = MemoryBus {
+            memory: memory, vram: [[0; VRAM_BANK]; 2], wram: [[0; WRAM_BANK]; 8], pc: 0x0,
+            bg_palettes: [0xFF; 64], obj_palettes: [0xFF; 64], sp: 0x0, ime: false, inf: 0x0, rom: rom, cheats: Cheats::new(), joypad: [0; 4], channels_on: 0, boot: None,
+            model: model, cgb_mode: false, oam_scan_row: 0, double_speed: false,
+            hdma: Hdma::new(), dma_stall: 0, oam_dma: OamDma::new(), dma_byte: 0xFF, sgb: None,
+        };
+        bus.set_model(model);
+        bus
+    }
+
+    pub fn set_model(&mut self, model: Model) {
+        self.model    = model;
+        self.cgb_mode = model.is_cgb() && self.rom.header.cgb != CgbSupport::None;
+        self.double_speed = false;
+        self.sgb = if model == Model::Sgb && self.rom.header.sgb { Some(Sgb::new()) } else { None };
+    }
+
+    fn io_exists(&self, addr: u16) -> bool {
+        self.model.is_cgb() || !CGB_IO.contains(&addr)
+    }
+
+    // Bank the CPU sees at 0x8000; DMG models only have bank 0
+    pub fn vram_bank(&self) -> usize {
+        if self.model.is_cgb() { (self.memory[VBK as usize] & 0x01) as usize } else { 0 }
+    }
+
+    // Bank the CPU sees at 0xD000; selecting bank 0 gets bank 1
+    pub fn wram_bank(&self) -> usize {
+        if self.model.is_cgb() { ((self.memory[SVBK as usize] & 0x07) as usize).max(1) } else { 1 }
+    }
+
+    // 0xC000-0xDFFF, with echo RAM folded back onto it
+    fn wram_slot(&self, addr: u16) -> (usize, usize) {
+        let addr = if addr >= ERAM_START { addr - (ERAM_START - WRAM_START) } else { addr };
+
+        if addr < WRAM_SWAP { (0, (addr - WRAM_START) as usize) }
+        else                { (self.wram_bank(), (addr - WRAM_SWAP) as usize) }
+    }
+
+    // Palette RAM is locked while the PPU is drawing
+    fn palettes_locked(&self) -> bool { self.memory[STAT as usize] & 0x03 == 0x03 }
+
+    fn read_palette(&self, spec: u16) -> u8 {
+        if self.palettes_locked() { return 0xFF; }
+
+        let index = (self.memory[spec as usize] & 0x3F) as usize;
+        if spec == BCPS { self.bg_palettes[index] } else { self.obj_palettes[index] }
+    }
+
+    // Writes go to the index in BCPS/OCPS, which steps forward when its bit 7 is set
+    fn write_palette(&mut self, spec: u16, val: u8) {
+        let index = self.memory[spec as usize] & 0x3F;
+
+        if !self.palettes_locked() {
+            if spec == BCPS { self.bg_palettes[index as usize] = val; } else { self.obj_palettes[index as usize] = val; }
+        }
+        if self.memory[spec as usize] & 0x80 != 0 {
+            self.memory[spec as usize] = 0x80 | (index + 1) & 0x3F;
+        }
+    }
+
+    // P14 low selects the directions, P15 low the buttons; pressed keys pull their line low. With
+    // SGB multiplayer on and neither selected, the low nibble is the current joypad's ID.
+    fn read_joypad(&self) -> u8 {
+        let select = self.memory[JOYP as usize] & 0x30;
+        let (player, players) = self.sgb.as_ref().map_or((0, 1), |sgb| (sgb.current_player(), sgb.players()));
+        let pressed = self.joypad[player as usize];
+
+        let mut lines = if players > 1 && select == 0x30 { 0x0F - player } else { 0x0F };
+        if select & 0x10 == 0 { lines &= !pressed & 0x0F; }
+        if select & 0x20 == 0 { lines &= !(pressed >> 4); }
+
+        0xC0 | select | lines
+    }
+
+    fn wave_ram_blocked(&self) -> bool {
+        self.model.wave_ram_locked() && self.channels_on & 0x04 != 0
+    }
+
+    // A trigger starts a channel if its DAC is on, turning the DAC off stops it. Length and
+    // sweep expiry belong to the APU.
+    fn update_sound_channels(&mut self, addr: u16) {
+        let powered = self.memory[NR52 as usize] & 0x80 != 0;
+
+        for (channel, &(dac, trigger)) in SOUND_CHANNELS.iter().enumerate() {
+            // Channel 3 has a DAC on/off bit, the others are off when volume and envelope are 0
+            let dac_on = if channel == 2 { self.memory[dac as usize] & 0x80 != 0 } else { self.memory[dac as usize] & 0xF8 != 0 };
+
+                 if !dac_on { self.channels_on &= !(1 << channel); }
+            else if addr == trigger && self.memory[trigger as usize] & 0x80 != 0 && powered { self.channels_on |= 1 << channel; }
+        }
+    }
 
+    // DMG OAM bug: a 16-bit increment/decrement with a pointer into 0xFE00-0xFEFF while the PPU
+    // scans OAM corrupts the row it is reading, mixing in the row before it
+    pub fn trigger_oam_bug(&mut self, addr: u16) {
+        if !self.model.has_oam_bug() || !(OAM_START..=0xFEFF).contains(&addr) { return; }
+        if self.memory[STAT as usize] & 0x03 != 0x02 || self.oam_scan_row == 0 { return; }
 
+        let row  = OAM_START as usize + self.oam_scan_row as usize * 8;
+        let prev = row - 8;
+        let word = |m: &[u8], at: usize| (m[at] as u16) | (m[at + 1] as u16) << 8;
 
-        MemoryBus { memory: memory, pc: 0x0, sp: 0x0, ime: false, inf: 0x0, rom: rom }
+        let a = word(&self.memory, row);
+        let b = word(&self.memory, prev);
+        let c = word(&self.memory, prev + 4);
+        let glitched = ((a ^ c) & (b ^ c)) ^ c;
+
+        self.memory[row]     = glitched as u8;
+        self.memory[row + 1] = (glitched >> 8) as u8;
+        for i in 2..8 { self.memory[row + i] = self.memory[prev + i]; }
+    }
+
+    // STOP with KEY1 armed flips the CGB between normal and double speed; returns whether it did
+    pub fn switch_speed(&mut self) -> bool {
+        if !self.model.is_cgb() || self.memory[KEY1 as usize] & 0x01 == 0 { return false; }
+
+        self.memory[KEY1 as usize] &= !0x01;
+        self.double_speed = !self.double_speed;
+        true
+    }
+
+    // Copy one 16 byte block into the current VRAM bank; the CPU sits out 8 M-cycles (16 in
+    // double speed) for every block
+    fn hdma_block(&mut self) -> bool {
+        let (src, dst) = (self.hdma.src, self.hdma.dst);
+        let bank = self.vram_bank();
+
+        for i in 0..BLOCK_SIZE {
+            let val = self.read_mapped(src.wrapping_add(i));
+            self.vram[bank][((dst + i) - VRAM_START) as usize] = val;
+        }
+
+        self.dma_stall += if self.double_speed { 64 } else { 32 };
+        self.hdma.advance()
+    }
+
+    fn start_hdma(&mut self, mode: HdmaMode) {
+        match mode {
+            HdmaMode::General => { while !self.hdma_block() { } }
+            // With the LCD off there are no HBlanks to wait for, one block goes right away
+            HdmaMode::HBlank if self.memory[LCDC as usize] & 0x80 == 0 => { self.hdma_block(); }
+            HdmaMode::HBlank => { }
+        }
+    }
+
+    // Advance OAM DMA by a number of M-cycles
+    pub fn step_oam_dma(&mut self, cycles: u16) {
+        for _ in 0..cycles {
+            let Some((src, index)) = self.oam_dma.step() else { continue; };
+
+            self.dma_byte = self.read_mapped(src);
+            self.memory[(OAM_START + index as u16) as usize] = self.dma_byte;
+        }
+    }
+
+    // During OAM DMA the CPU only gets at HRAM and the I/O registers; OAM reads 0xFF and
+    // anything else returns whatever the DMA is moving over the bus
+    fn dma_conflict(&self, addr: u16) -> Option<u8> {
+        if !self.oam_dma.is_active() || addr >= IORG_START { return None; }
+        Some(if (OAM_START..=OAM_END).contains(&addr) { 0xFF } else { self.dma_byte })
+    }
+
+    // Called by the PPU every time it enters HBlank
+    pub fn hblank(&mut self) {
+        if self.hdma.active { self.hdma_block(); }
+    }
+
+    // Map a boot ROM over the cartridge and start executing it from the top
+    pub fn map_boot_rom(&mut self, boot: BootRom) {
+        self.boot = Some(boot);
+        self.pc   = 0x0000;
+        self.double_speed = false;
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool { self.boot.is_some() }
+
+    // Set I/O registers to what the boot ROM would have left behind, without triggering any of
+    // the side effects a CPU write would have
+    pub fn load_io(&mut self, values: &[(u16, u8)]) {
+        for &(addr, val) in values {
+            self.memory[addr as usize] = val;
+        }
+
+        // The status bits live with the channels, NR52 itself only keeps the power bit
+        self.channels_on = self.memory[NR52 as usize] & 0x0F;
+        self.memory[NR52 as usize] &= 0x80;
     }
 
     pub fn read_byte(&self, addr: u16) -> u8 {
+        self.dma_conflict(addr).unwrap_or_else(|| self.read_mapped(addr))
+    }
+
+    pub fn write_byte(&mut self, addr: u16, val: u8) {
+        if self.dma_conflict(addr).is_none() { self.write_mapped(addr, val); }
+    }
+
+    // The memory map as seen by DMA and the cheat engine, without OAM DMA bus conflicts
+    fn read_mapped(&self, addr: u16) -> u8 {
         match addr {
             // ROM_START..=ROM_END => { self.rom.read_byte(addr) }
             // VROM_START..=VROM_END => { }
@@ -55,42 +287,83 @@ impl MemoryBus {
             // OAM_START..=OAM_END     => { self.sup.ppu.read_oam(addr - OAM_START) }
             // WVRAM_START..=WVRAM_END => { self.sup.apu.read_wvram(addr - WVRAM_START) }
 
-            // CRAM_START..=CRAM_END => { }
-            ROM_START..=VROM_END => { self.rom.read_byte(addr) }
+            ROM_START..=VROM_END if self.boot.as_ref().is_some_and(|b| b.covers(addr)) => {
+                self.boot.as_ref().unwrap().read_byte(addr)
+            }
+            ROM_START..=VROM_END => { self.cheats.patch_rom(addr, self.rom.read_byte(addr)) }
+            VRAM_START..=VRAM_END => { self.vram[self.vram_bank()][(addr - VRAM_START) as usize] }
+            CRAM_START..=CRAM_END => { self.rom.read_ram(addr) }
+            WRAM_START..=ERAM_END => { let (bank, at) = self.wram_slot(addr); self.wram[bank][at] }
             UNUSED..=UNUSED_D => { 0x00 }
+            BOOT_OFF => { 0xFF }
+            IORG_START..=IORG_END if !self.io_exists(addr) => { 0xFF }
+            JOYP => { self.read_joypad() }
+            NR52 => { 0x70 | self.memory[addr as usize] | self.channels_on }
+            KEY1 => { 0x7E | (self.double_speed as u8) << 7 | self.memory[addr as usize] & 0x01 }
+            VBK  => { 0xFE | self.memory[addr as usize] }
+            SVBK => { 0xF8 | self.memory[addr as usize] }
+            BCPS | OCPS => { 0x40 | self.memory[addr as usize] }
+            HDMA1..=HDMA5 => { self.hdma.read(addr) }
+            BCPD => { self.read_palette(BCPS) }
+            OCPD => { self.read_palette(OCPS) }
+            WVRAM_START..=WVRAM_END if self.wave_ram_blocked() => { 0xFF }
             _ => self.memory[addr as usize]
         }
         // return self.memory[addr as usize];
     }
 
-    pub fn write_byte(&mut self, addr: u16, val: u8) {
+    fn write_mapped(&mut self, addr: u16, val: u8) {
         match addr {
-            ROM_START..=VROM_END => { } // Can't write to ROM
+            ROM_START..=VROM_END => { self.rom.write_byte(addr, val) } // Mapper control registers
             // VRAM_START..=VRAM_END => { self.sup.ppu.write_vram(addr - VRAM_START, val) }
             // OAM_START..=OAM_END   => { self.sup.ppu.write_oam(addr - OAM_START, val) }
             
-            CRAM_START..=CRAM_END => { }
-            WRAM_START..=WRAM_END => {
-                self.memory[addr as usize] = val;
-                // Write to ECHO ram as well
-                let eloc = addr - WRAM_START + ERAM_START;
-                if eloc <= ERAM_END {
-                    self.write_byte(eloc, val);
-                }
-            }
+            VRAM_START..=VRAM_END => { let bank = self.vram_bank(); self.vram[bank][(addr - VRAM_START) as usize] = val }
+            CRAM_START..=CRAM_END => { self.rom.write_ram(addr, val) }
+            WRAM_START..=ERAM_END => { let (bank, at) = self.wram_slot(addr); self.wram[bank][at] = val }
             UNUSED..=UNUSED_D => { } // Can't write to unmapped location
+            BOOT_OFF => { if val != 0 { self.boot = None; } } // Boot ROM unmaps itself for good
+            IORG_START..=IORG_END if !self.io_exists(addr) => { }
+            NR52 => { // Only the power bit is writable, powering off stops every channel
+                self.memory[addr as usize] = val & 0x80;
+                if val & 0x80 == 0 { self.channels_on = 0; }
+            }
+            NR10..=NR51 => { self.memory[addr as usize] = val; self.update_sound_channels(addr); }
+            KEY1 => { self.memory[addr as usize] = val & 0x01 } // Only the arm bit is writable
+            VBK  => { self.memory[addr as usize] = val & 0x01 }
+            SVBK => { self.memory[addr as usize] = val & 0x07 }
+            BCPS | OCPS => { self.memory[addr as usize] = val & 0xBF }
+            HDMA1..=HDMA5 => { if let Some(mode) = self.hdma.write(addr, val) { self.start_hdma(mode) } }
+            OAM_DMA => { self.memory[addr as usize] = val; self.oam_dma.start(val); }
+            JOYP => {
+                self.memory[addr as usize] = val & 0x30;
+                if let Some(sgb) = self.sgb.as_mut() { sgb.write_joypad(val); }
+            }
+            BCPD => { self.write_palette(BCPS, val) }
+            OCPD => { self.write_palette(OCPS, val) }
+            WVRAM_START..=WVRAM_END if self.wave_ram_blocked() => { }
             // WVRAM_START..=WVRAM_END => { self.sup.apu.write_wvram(addr - WVRAM_START, val)}
             _ => self.memory[addr as usize] = val,
         }        
     }
 
-    pub fn write_rom(&mut self) {}
+    // GameShark codes poke RAM once per frame, the way the real device does from VBlank
+    pub fn apply_cheats(&mut self) {
+        for (bank, addr, val) in self.cheats.ram_writes() {
+            match (bank, addr) {
+                (Some(bank), WRAM_SWAP..=WRAM_END) if self.model.is_cgb() => {
+                    self.wram[(bank as usize).max(1)][(addr - WRAM_SWAP) as usize] = val;
+                }
+                _ => self.write_mapped(addr, val),
+            }
+        }
+    }
 
     pub fn read_increment(&mut self) -> u8 {
-        let data = self.memory[self.pc as usize];
+        let data = self.read_byte(self.pc);
         self.pc = self.pc.wrapping_add(1);
 
-        return data;
+        data
     }
 
     pub fn jump(&mut self, target: u16, condition: bool) {
@@ -112,6 +385,94 @@ impl MemoryBus {
         let msb = self.read_byte(self.sp) as u16;
         self.sp = self.sp.wrapping_add(1);
 
-        return (msb << 8) | lsb;
+        (msb << 8) | lsb
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::cartridge::tests::rom_image;
+    use crate::sgb::tests::send_packet;
+
+    pub(crate) fn bus(model: Model) -> MemoryBus {
+        let mut bus = MemoryBus::new(ROM::from_bytes(rom_image(0x00, 0, 0)).unwrap());
+        bus.set_model(model);
+        bus.load_io(&model.post_boot_io());
+        bus
+    }
+
+    #[test]
+    fn post_boot_serial_control() {
+        assert_eq!(bus(Model::Dmg).read_byte(0xFF02), 0x7E);
+        assert_eq!(bus(Model::Cgb).read_byte(0xFF02), 0x7F);
+    }
+
+    #[test]
+    fn nr52_only_takes_the_power_bit() {
+        let mut bus = bus(Model::Dmg);
+        assert_eq!(bus.read_byte(NR52), 0xF1);   // Boot chime left channel 1 on
+
+        bus.write_byte(NR52, 0x8F);
+        assert_eq!(bus.read_byte(NR52), 0xF1);
+
+        bus.write_byte(NR52, 0x0F);
+        assert_eq!(bus.read_byte(NR52), 0x70);
+    }
+
+    #[test]
+    fn nr52_reports_channel_3() {
+        let mut bus = bus(Model::Dmg);
+        bus.write_byte(0xFF1A, 0x80);
+        bus.write_byte(0xFF1E, 0x80);
+        assert_eq!(bus.read_byte(NR52), 0xF5);
+        assert_eq!(bus.read_byte(WVRAM_START), 0xFF);    // DMG wave RAM is locked while it plays
+
+        bus.write_byte(0xFF1A, 0x00);
+        assert_eq!(bus.read_byte(NR52), 0xF1);
+
+        // Triggering with the DAC off or the APU powered down does nothing
+        bus.write_byte(0xFF1E, 0x80);
+        bus.write_byte(NR52, 0x00);
+        bus.write_byte(0xFF1A, 0x80);
+        bus.write_byte(0xFF1E, 0x80);
+        assert_eq!(bus.read_byte(NR52), 0x70);
+    }
+
+    #[test]
+    fn joypad_reads_follow_the_select_lines() {
+        let mut bus = bus(Model::Dmg);
+        bus.joypad[0] = 0x11;   // Right and A
+
+        bus.write_byte(JOYP, 0x20);
+        assert_eq!(bus.read_byte(JOYP), 0xEE);
+        bus.write_byte(JOYP, 0x10);
+        assert_eq!(bus.read_byte(JOYP), 0xDE);
+        bus.write_byte(JOYP, 0x00);
+        assert_eq!(bus.read_byte(JOYP), 0xCE);
+        bus.write_byte(JOYP, 0xFF);
+        assert_eq!(bus.read_byte(JOYP), 0xFF);
+
+        // Releasing a key shows up without touching JOYP again
+        bus.write_byte(JOYP, 0x20);
+        bus.joypad[0] = 0x00;
+        assert_eq!(bus.read_byte(JOYP), 0xEF);
+    }
+
+    #[test]
+    fn joypad_reads_sgb_multiplayer_ids() {
+        let mut bus = bus(Model::Dmg);
+        bus.sgb = Some(Sgb::new());
+        bus.joypad[1] = 0x01;   // Player 2 holds right
+
+        send_packet(|val| bus.write_byte(JOYP, val), &[0x11 << 3 | 1, 0x01]);
+        assert_eq!(bus.read_byte(JOYP), 0xFF);
+
+        // Pulsing P15 moves on to the next joypad
+        bus.write_byte(JOYP, 0x10);
+        bus.write_byte(JOYP, 0x30);
+        assert_eq!(bus.read_byte(JOYP), 0xFE);
+        bus.write_byte(JOYP, 0x20);
+        assert_eq!(bus.read_byte(JOYP), 0xEE);
     }
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+use std::str::FromStr;
+
+use crate::cartridge::{CartridgeHeader, CgbSupport};
+
+// Game Boy hardware revisions. Besides the boot state, the model decides which I/O registers
+// exist, a couple of DMG-only hardware bugs and whether CGB features are available at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Model { Dmg0, Dmg, Mgb, Sgb, Cgb, Agb }
+
+// CPU and I/O state the boot ROM leaves behind when it jumps to 0x0100
+pub(crate) struct PostBoot {
+    pub af:  u16,
+    pub bc:  u16,
+    pub de:  u16,
+    pub hl:  u16,
+    pub sp:  u16,
+    pub div: u16,   // Internal 16-bit divider, DIV (0xFF04) is the upper byte
+}
+
+// Values every model agrees on, from Pan Docs' "Power Up Sequence" table
+const IO_COMMON: [(u16, u8); 37] = [
+    (0xFF00, 0xCF), (0xFF01, 0x00), (0xFF05, 0x00), (0xFF06, 0x00), (0xFF07, 0xF8),
+    (0xFF0F, 0xE1),
+    (0xFF10, 0x80), (0xFF11, 0xBF), (0xFF12, 0xF3), (0xFF13, 0xFF), (0xFF14, 0xBF),
+    (0xFF16, 0x3F), (0xFF17, 0x00), (0xFF18, 0xFF), (0xFF19, 0xBF),
+    (0xFF1A, 0x7F), (0xFF1B, 0xFF), (0xFF1C, 0x9F), (0xFF1D, 0xFF), (0xFF1E, 0xBF),
+    (0xFF20, 0xFF), (0xFF21, 0x00), (0xFF22, 0x00), (0xFF23, 0xBF),
+    (0xFF24, 0x77), (0xFF25, 0xF3),
+    (0xFF40, 0x91), (0xFF42, 0x00), (0xFF43, 0x00), (0xFF44, 0x00), (0xFF45, 0x00),
+    (0xFF46, 0xFF), (0xFF47, 0xFC), (0xFF48, 0x00), (0xFF49, 0x00), (0xFF4A, 0x00), (0xFF4B, 0x00),
+];
+
+// Registers only the CGB has
+const IO_CGB: [(u16, u8); 4] = [
+    (0xFF4D, 0x7E), (0xFF4F, 0xFE), (0xFF55, 0xFF), (0xFF70, 0xF8),
+];
+
+impl Model {
+    // What a real console would most likely be running this cart on
+    pub fn for_header(header: &CartridgeHeader) -> Model {
+             if header.cgb != CgbSupport::None { Model::Cgb }
+        else if header.sgb                     { Model::Sgb }
+        else                                   { Model::Dmg }
+    }
+
+    pub fn is_cgb(&self) -> bool { matches!(self, Model::Cgb | Model::Agb) }
+
+    // 16-bit INC/DEC (and friends) on a pointer into OAM during OAM scan trash sprite data
+    pub fn has_oam_bug(&self) -> bool { !self.is_cgb() }
+
+    // Wave RAM is off limits to the CPU while channel 3 plays, CGB lets it through
+    pub fn wave_ram_locked(&self) -> bool { !self.is_cgb() }
+
+    // header_checksum sets H and C on DMG/MGB; cgb_game picks between CGB and compatibility mode
+    pub(crate) fn post_boot(&self, header_checksum: u8, cgb_game: bool) -> PostBoot {
+        use Model::*;
+
+        // The DMG boot ROM leaves H and C set unless the header checksum happened to be zero
+        let hc: u16 = if header_checksum == 0 { 0x80 } else { 0xB0 };
+
+        let (af, bc, de, hl, div) = match self {
+            Dmg0 => (0x0100, 0xFF13, 0x00C1, 0x8403, 0x1830),
+            Dmg  => (0x0100 | hc, 0x0013, 0x00D8, 0x014D, 0xABCC),
+            Mgb  => (0xFF00 | hc, 0x0013, 0x00D8, 0x014D, 0xABCC),
+            Sgb  => (0x0100, 0x0014, 0x0000, 0xC060, 0xD85C),
+            Cgb if cgb_game => (0x1180, 0x0000, 0xFF56, 0x000D, 0x267C),
+            Cgb  => (0x1180, 0x0000, 0x0008, 0x007C, 0x267C),
+            // The GBA boot ROM does one extra INC B, which is how games tell it apart from a CGB
+            Agb if cgb_game => (0x1100, 0x0100, 0xFF56, 0x000D, 0x267C),
+            Agb  => (0x1100, 0x0100, 0x0008, 0x007C, 0x267C),
+        };
+
+        PostBoot { af, bc, de, hl, sp: 0xFFFE, div }
+    }
+
+    pub(crate) fn post_boot_io(&self) -> Vec<(u16, u8)> {
+        use Model::*;
+
+        let mut io = IO_COMMON.to_vec();
+
+        // STAT comes out in a different mode depending on how long the boot ROM ran, and the
+        // SGB leaves sound channel 1 off
+        io.push((0xFF41, if *self == Dmg0 { 0x81 } else { 0x85 }));
+        io.push((0xFF26, match self { Sgb => 0xF0, _ => 0xF1 }));
+
+        // SC bit 1 (the CGB's fast serial clock) doesn't exist on older models and reads as 1
+        io.push((0xFF02, if self.is_cgb() { 0x7F } else { 0x7E }));
+
+        if self.is_cgb() { io.extend_from_slice(&IO_CGB); }
+
+        io
+    }
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "dmg0" => Ok(Model::Dmg0),
+            "dmg"  => Ok(Model::Dmg),
+            "mgb"  => Ok(Model::Mgb),
+            "sgb"  => Ok(Model::Sgb),
+            "cgb"  => Ok(Model::Cgb),
+            "agb"  => Ok(Model::Agb),
+            _      => Err(format!("unknown model {} (expected dmg0, dmg, mgb, sgb, cgb or agb)", s)),
+        }
+    }
+}
@@ -0,0 +1,38 @@
+pub const OAM_DMA: u16 = 0xFF46;
+pub const OAM_SIZE: u8 = 0xA0;
+
+// OAM DMA copies 160 bytes from XX00 into OAM, one byte per M-cycle. A new transfer needs one
+// M-cycle to get going, so retriggering mid-transfer lets the old one run for one more byte.
+#[derive(Default)]
+pub struct OamDma {
+    active:  Option<(u16, u8)>,    // Source and the next byte to copy
+    pending: Option<u16>,          // Source of a transfer that's still starting up
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        OamDma { active: None, pending: None }
+    }
+
+    // Sources past 0xDFFF read back through echo RAM
+    pub fn start(&mut self, page: u8) {
+        let src = (page as u16) << 8;
+        self.pending = Some(if src >= 0xE000 { src - 0x2000 } else { src });
+    }
+
+    pub fn is_active(&self) -> bool { self.active.is_some() }
+
+    // Run one M-cycle; returns the (source address, OAM offset) to copy this cycle
+    pub fn step(&mut self) -> Option<(u16, u8)> {
+        let copy = self.active.map(|(src, index)| (src + index as u16, index));
+
+        self.active = match self.active {
+            Some((src, index)) if index + 1 < OAM_SIZE => Some((src, index + 1)),
+            _ => None,
+        };
+
+        if let Some(src) = self.pending.take() { self.active = Some((src, 0)); }
+
+        copy
+    }
+}
@@ -0,0 +1,403 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use flate2::Crc;
+
+const IPS_MAGIC:    &[u8] = b"PATCH";
+const IPS_EOF:      &[u8] = b"EOF";
+const UPS_MAGIC:    &[u8] = b"UPS1";
+const BPS_MAGIC:    &[u8] = b"BPS1";
+
+// Source, target and patch CRC32s trail every UPS/BPS file
+const FOOTER_SIZE:  usize = 12;
+
+// Largest cartridge there is (MBC5, 512 banks); sizes in a patch header aren't trusted past it
+const MAX_TARGET_SIZE: usize = 0x80_0000;
+
+// Looked for next to the ROM when no patch is given explicitly
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    UnknownFormat,
+    Malformed(&'static str),
+    TooLarge(usize),
+    SourceChecksum { expected: u32, actual: u32 },
+    TargetChecksum { expected: u32, actual: u32 },
+    PatchChecksum  { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use PatchError::*;
+
+        match self {
+            UnknownFormat                       => write!(f, "not an IPS, UPS or BPS patch"),
+            Malformed(what)                     => write!(f, "malformed patch: {}", what),
+            TooLarge(size)                      => write!(f, "patched ROM would be {} bytes, more than any cartridge holds", size),
+            SourceChecksum { expected, actual } => write!(f, "patch is for a different ROM (CRC32 {:08X}, ROM is {:08X})", expected, actual),
+            TargetChecksum { expected, actual } => write!(f, "patched ROM CRC32 {:08X} doesn't match expected {:08X}", actual, expected),
+            PatchChecksum  { expected, actual } => write!(f, "patch file is corrupt (CRC32 {:08X}, expected {:08X})", actual, expected),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+pub fn find_beside(rom_path: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS.iter()
+        .map(|ext| rom_path.with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+         if patch.starts_with(IPS_MAGIC) { apply_ips(rom, patch) }
+    else if patch.starts_with(UPS_MAGIC) { apply_ups(rom, patch) }
+    else if patch.starts_with(BPS_MAGIC) { apply_bps(rom, patch) }
+    else { Err(PatchError::UnknownFormat) }
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+struct Reader<'a> { data: &'a [u8], pos: usize }
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self { Reader { data, pos } }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        let byte = *self.data.get(self.pos).ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let bytes = self.data.get(self.pos..).and_then(|rest| rest.get(..len)).ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn be(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self.bytes(len)?.iter().fold(0, |n, b| n << 8 | *b as usize))
+    }
+
+    // UPS/BPS variable-length integer: 7 bits per byte, high bit ends it, with an implicit +1 per
+    // continuation so every value has exactly one encoding
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value = 0usize;
+        let mut shift = 1usize;
+
+        loop {
+            let byte = self.byte()?;
+            value = value.checked_add((byte & 0x7F) as usize * shift).ok_or(PatchError::Malformed("integer overflow"))?;
+            if byte & 0x80 != 0 { return Ok(value); }
+
+            shift = shift.checked_shl(7).ok_or(PatchError::Malformed("integer overflow"))?;
+            value += shift;
+        }
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut out    = rom.to_vec();
+    let mut reader = Reader::new(patch, IPS_MAGIC.len());
+
+    loop {
+        if reader.data[reader.pos..].starts_with(IPS_EOF) {
+            reader.pos += IPS_EOF.len();
+            break;
+        }
+
+        let offset = reader.be(3)?;
+        let size   = reader.be(2)?;
+
+        // Zero size means a run-length record
+        let (len, fill) = if size == 0 { (reader.be(2)?, Some(reader.byte()?)) } else { (size, None) };
+        if out.len() < offset + len { out.resize(check_size(offset + len)?, 0); }
+
+        match fill {
+            Some(value) => out[offset..offset + len].fill(value),
+            None        => out[offset..offset + len].copy_from_slice(reader.bytes(len)?),
+        }
+    }
+
+    // Optional truncation extension
+    if patch.len() - reader.pos == 3 { out.truncate(reader.be(3)?); }
+
+    Ok(out)
+}
+
+fn check_size(size: usize) -> Result<usize, PatchError> {
+    if size <= MAX_TARGET_SIZE { Ok(size) } else { Err(PatchError::TooLarge(size)) }
+}
+
+fn check_footer(rom: &[u8], patch: &[u8]) -> Result<(u32, u32), PatchError> {
+    if patch.len() < FOOTER_SIZE + 4 { return Err(PatchError::Malformed("patch too short")); }
+
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let word   = |i: usize| u32::from_le_bytes(footer[i * 4..i * 4 + 4].try_into().unwrap());
+
+    let actual = crc32(&patch[..patch.len() - 4]);
+    if actual != word(2) { return Err(PatchError::PatchChecksum { expected: word(2), actual }); }
+
+    let actual = crc32(rom);
+    if actual != word(0) { return Err(PatchError::SourceChecksum { expected: word(0), actual }); }
+
+    Ok((word(0), word(1)))
+}
+
+fn check_target(out: &[u8], expected: u32) -> Result<(), PatchError> {
+    let actual = crc32(out);
+    if actual == expected { Ok(()) } else { Err(PatchError::TargetChecksum { expected, actual }) }
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (_, target_crc) = check_footer(rom, patch)?;
+    let end = patch.len() - FOOTER_SIZE;
+
+    let mut reader = Reader::new(&patch[..end], UPS_MAGIC.len());
+    let _source_size = reader.varint()?;
+    let target_size  = check_size(reader.varint()?)?;
+
+    // Output starts as the source, hunks XOR their bytes in place
+    let mut out = rom.to_vec();
+    out.resize(target_size, 0);
+
+    let mut pos = 0usize;
+    while reader.pos < end {
+        pos = pos.saturating_add(reader.varint()?);
+        loop {
+            let xor = reader.byte()?;
+            if pos < out.len() { out[pos] ^= xor; }
+            pos += 1;
+            if xor == 0 { break; }
+        }
+    }
+
+    check_target(&out, target_crc)?;
+    Ok(out)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (_, target_crc) = check_footer(rom, patch)?;
+    let end = patch.len() - FOOTER_SIZE;
+
+    let mut reader = Reader::new(&patch[..end], BPS_MAGIC.len());
+    let _source_size  = reader.varint()?;
+    let target_size   = check_size(reader.varint()?)?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+
+    let mut out: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_rel   = 0isize;
+    let mut target_rel   = 0isize;
+
+    let relative = |reader: &mut Reader, base: &mut isize| -> Result<usize, PatchError> {
+        let data  = reader.varint()?;
+        let delta = (data >> 1) as isize;
+        *base = base.checked_add(if data & 1 != 0 { -delta } else { delta }).ok_or(PatchError::Malformed("integer overflow"))?;
+        usize::try_from(*base).map_err(|_| PatchError::Malformed("copy offset before start"))
+    };
+
+    while reader.pos < end {
+        let data = reader.varint()?;
+        let len  = (data >> 2) + 1;
+        if len > target_size - out.len() { return Err(PatchError::Malformed("output larger than header says")); }
+
+        match data & 0x03 {
+            // SourceRead: same offset in the source as in the output
+            0 => {
+                let from = out.len();
+                out.extend_from_slice(rom.get(from..from + len).ok_or(PatchError::Malformed("source read past end"))?);
+            }
+            // TargetRead: literal bytes from the patch
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            // SourceCopy
+            2 => {
+                let from = relative(&mut reader, &mut source_rel)?;
+                out.extend_from_slice(rom.get(from..).and_then(|rest| rest.get(..len)).ok_or(PatchError::Malformed("source copy past end"))?);
+                source_rel += len as isize;
+            }
+            // TargetCopy: byte by byte since the ranges may overlap
+            _ => {
+                let from = relative(&mut reader, &mut target_rel)?;
+                if from >= out.len() { return Err(PatchError::Malformed("target copy past end")); }
+                for i in from..from + len {
+                    let byte = *out.get(i).ok_or(PatchError::Malformed("target copy past end"))?;
+                    out.push(byte);
+                }
+                target_rel += len as isize;
+            }
+        }
+    }
+
+    if out.len() != target_size { return Err(PatchError::Malformed("output size doesn't match header")); }
+
+    check_target(&out, target_crc)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(out: &mut Vec<u8>, mut n: usize) {
+        loop {
+            let bits = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 { out.push(0x80 | bits); return; }
+            out.push(bits);
+            n -= 1;
+        }
+    }
+
+    fn with_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32(source).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let crc = crc32(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+        patch
+    }
+
+    // XOR hunks for every run of differing bytes
+    fn ups(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = UPS_MAGIC.to_vec();
+        varint(&mut patch, source.len());
+        varint(&mut patch, target.len());
+
+        let xor = |i: usize| source.get(i).copied().unwrap_or(0) ^ target[i];
+        let (mut pos, mut i) = (0, 0);
+        while i < target.len() {
+            if xor(i) == 0 { i += 1; continue; }
+
+            varint(&mut patch, i - pos);
+            while i < target.len() && xor(i) != 0 { patch.push(xor(i)); i += 1; }
+            patch.push(0);
+            pos = i + 1;
+            i  += 1;
+        }
+
+        with_footer(patch, source, target)
+    }
+
+    fn bps_command(patch: &mut Vec<u8>, kind: usize, len: usize) {
+        varint(patch, (len - 1) << 2 | kind);
+    }
+
+    #[test]
+    fn reads_varints() {
+        for n in [0, 1, 0x7F, 0x80, 0x407F, 0x4080, 0x80_0000] {
+            let mut data = Vec::new();
+            varint(&mut data, n);
+            assert_eq!(Reader::new(&data, 0).varint(), Ok(n));
+        }
+    }
+
+    #[test]
+    fn applies_ips_records_and_truncation() {
+        let rom = [0u8; 16];
+        let mut patch = IPS_MAGIC.to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0xAA, 0xBB]);   // Plain record
+        patch.extend_from_slice(&[0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x04, 0xCC]);   // RLE past the end
+        patch.extend_from_slice(IPS_EOF);
+
+        let out = apply(&rom, &patch).unwrap();
+        assert_eq!(out.len(), 20);
+        assert_eq!(&out[..4], &[0x00, 0x00, 0xAA, 0xBB]);
+        assert_eq!(&out[16..], &[0xCC; 4]);
+
+        patch.extend_from_slice(&[0x00, 0x00, 0x03]);
+        assert_eq!(apply(&rom, &patch).unwrap(), vec![0x00, 0x00, 0xAA]);
+    }
+
+    #[test]
+    fn rejects_oversized_ips() {
+        let mut patch = IPS_MAGIC.to_vec();
+        patch.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x00]);
+        patch.extend_from_slice(IPS_EOF);
+
+        assert_eq!(apply(&[0; 16], &patch), Err(PatchError::TooLarge(0xFFFFFF + 0xFFFF)));
+    }
+
+    #[test]
+    fn round_trips_ups() {
+        let source = b"hello world".to_vec();
+        for target in [&b"hello there!"[..], &b"jello world"[..], &b"help"[..]] {
+            assert_eq!(apply(&source, &ups(&source, target)).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn checks_ups_crcs() {
+        let (source, target) = (b"hello world", b"hello there");
+        let patch = ups(source, target);
+
+        assert!(matches!(apply(b"jello world", &patch), Err(PatchError::SourceChecksum { .. })));
+
+        let mut corrupt = patch.clone();
+        corrupt[6] ^= 1;
+        assert!(matches!(apply(source, &corrupt), Err(PatchError::PatchChecksum { .. })));
+
+        // Consistent patch, but the target CRC is for something else
+        let body = patch[..patch.len() - FOOTER_SIZE].to_vec();
+        let wrong = with_footer(body, source, b"hello thera");
+        assert!(matches!(apply(source, &wrong), Err(PatchError::TargetChecksum { .. })));
+    }
+
+    #[test]
+    fn rejects_oversized_ups() {
+        let source = [0u8; 4];
+        let mut patch = UPS_MAGIC.to_vec();
+        varint(&mut patch, source.len());
+        varint(&mut patch, 0x4000_0000);
+
+        assert_eq!(apply(&source, &with_footer(patch, &source, &[])), Err(PatchError::TooLarge(0x4000_0000)));
+    }
+
+    #[test]
+    fn round_trips_bps() {
+        let source = b"ABCDEFGH";
+        let target = b"ABCDxyEFxyEFFFF";
+
+        let mut patch = BPS_MAGIC.to_vec();
+        varint(&mut patch, source.len());
+        varint(&mut patch, target.len());
+        varint(&mut patch, 0);
+        bps_command(&mut patch, 0, 4);                              // ABCD
+        bps_command(&mut patch, 1, 2);                              // xy
+        patch.extend_from_slice(b"xy");
+        bps_command(&mut patch, 2, 2); varint(&mut patch, 4 << 1);  // EF from source offset 4
+        bps_command(&mut patch, 3, 4); varint(&mut patch, 4 << 1);  // xyEF from output offset 4
+        bps_command(&mut patch, 3, 3); varint(&mut patch, 3 << 1);  // FFF, overlapping itself from 11
+
+        let patch = with_footer(patch, source, target);
+        assert_eq!(apply(source, &patch).unwrap(), target);
+
+        let mut corrupt = patch.clone();
+        corrupt[8] ^= 1;
+        assert!(matches!(apply(source, &corrupt), Err(PatchError::PatchChecksum { .. })));
+        assert!(matches!(apply(b"ABCDEFGX", &patch), Err(PatchError::SourceChecksum { .. })));
+    }
+
+    #[test]
+    fn rejects_oversized_bps() {
+        let source = [0u8; 4];
+        let mut patch = BPS_MAGIC.to_vec();
+        varint(&mut patch, source.len());
+        varint(&mut patch, usize::MAX >> 8);
+        varint(&mut patch, 0);
+
+        assert_eq!(apply(&source, &with_footer(patch.clone(), &source, &[])), Err(PatchError::TooLarge(usize::MAX >> 8)));
+
+        // Within the limit, but the commands write more than the header promised
+        let mut patch = BPS_MAGIC.to_vec();
+        varint(&mut patch, source.len());
+        varint(&mut patch, 2);
+        varint(&mut patch, 0);
+        bps_command(&mut patch, 1, 0x10_0000);
+        assert_eq!(apply(&source, &with_footer(patch, &source, &[])), Err(PatchError::Malformed("output larger than header says")));
+    }
+}
@@ -1,10 +1,8 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
-use crate::{emulator::Screen, memory::MemoryBus};
-use pixels::Pixels;
+use crate::{colorize::{Colorization, DmgColors}, memory::MemoryBus, sgb::{Sgb, Mask, SCREEN_WIDTH, SCREEN_HEIGHT, GAME_X, GAME_Y}, utils::rgb555};
 
-const VRAM_BEGIN:   u16 = 0x8000;
 const OAM_BEGIN:    u16 = 0xFE00;
 
 // #[derive(Copy,Clone)]
@@ -15,7 +13,14 @@ const OAM_BEGIN:    u16 = 0xFE00;
 //     [[TilePixelValue::Zero; 8]; 8]
 // }
 
+// Where finished frames go: a window, a recorder, a test harness... Pixels are 0xRRGGBB, row by
+// row; the size is 160x144, or 256x224 with a Super Game Boy border.
+pub trait FrameSink {
+    fn present(&mut self, frame: &[u32], width: usize, height: usize);
+}
+
 // enum PPUModes { HBlank, VBlank, OamSearch, PixelTransfer }
+#[derive(Clone, Copy, PartialEq, Eq)]
 pub(crate) enum PPUSettings { 
     LCDC = 0xFF40, STAT = 0xFF41, SCY  = 0xFF42, SCX  = 0xFF43,
     LY   = 0xFF44, LYC  = 0xFF45, DMA  = 0xFF46, BGP  = 0xFF47,
@@ -24,29 +29,50 @@ pub(crate) enum PPUSettings {
 
 pub(crate) struct PPU {
     cycle_count: u16,
-    scanline:    u32,
     buffer:    [[u32; 160]; 144],
-    pixels:      Rc<RefCell<Pixels>>,
+    bg_line:     [(u8, bool); 160],     // BG/window color id and CGB priority bit behind each pixel
+    dmg_colors:  Option<DmgColors>,
+    window_line: u8,                    // Window rows drawn so far this frame
+    frame:       Vec<u32>,              // Last finished frame, border included
+    sink:        Option<Box<dyn FrameSink>>,
     bus:         Rc<RefCell<MemoryBus>>,
 }
 
 impl PPU {
-    pub fn new(mem: Rc<RefCell<MemoryBus>>, pxl: Rc<RefCell<Pixels>>) -> Self {
+    pub fn new(mem: Rc<RefCell<MemoryBus>>) -> Self {
+        let dmg_colors = Colorization::Auto.colors(&mem.borrow().rom.header);
+
         PPU {
             cycle_count: 0,
-            scanline:    0,
             // mode:        PPUModes::HBlank,
             buffer:    [[0x00; 160]; 144],
-            pixels:      pxl,
+            bg_line:     [(0, false); 160],
+            dmg_colors:  dmg_colors,
+            window_line: 0,
+            frame:       vec![0; 160 * 144],
+            sink:        None,
             bus:         mem,
         }
     }
 
-    pub fn read_vram(&self, addr: u16) -> u8 { self.bus.borrow().read_byte(addr + VRAM_BEGIN) }
-    pub fn write_vram(&mut self, addr: u16, value: u8) { self.bus.borrow_mut().write_byte(addr + VRAM_BEGIN, value) }
+    // The PPU reads both VRAM banks directly, whatever bank the CPU has selected; bank 1 holds
+    // the CGB tile attributes and extra tile data
+    pub fn read_vram(&self, bank: usize, addr: u16) -> u8 { self.bus.borrow().vram[bank][addr as usize] }
+    #[cfg(test)]
+    pub fn write_vram(&mut self, bank: usize, addr: u16, value: u8) { self.bus.borrow_mut().vram[bank][addr as usize] = value }
+
+    // Straight from OAM, so a DMA in flight doesn't hide it from the PPU
+    pub fn read_oam(&self, addr: u16) -> u8 { self.bus.borrow().memory[(addr + OAM_BEGIN) as usize] }
+    #[cfg(test)]
+    pub fn write_oam(&mut self, addr: u16, value: u8) { self.bus.borrow_mut().memory[(addr + OAM_BEGIN) as usize] = value }
 
-    pub fn read_oam(&self, addr: u16) -> u8 { self.bus.borrow().read_byte(addr + OAM_BEGIN) }
-    pub fn write_oam(&mut self, addr: u16, value: u8) { self.bus.borrow_mut().write_byte(addr + OAM_BEGIN, value) }
+    pub fn set_frame_sink(&mut self, sink: Box<dyn FrameSink>) { self.sink = Some(sink); }
+
+    pub fn frame(&self) -> &[u32] { &self.frame }
+
+    pub fn set_colorization(&mut self, colorization: Colorization) {
+        self.dmg_colors = colorization.colors(&self.bus.borrow().rom.header);
+    }
 
     pub fn get(&self, setting: PPUSettings) -> u8 { self.bus.borrow().read_byte(setting as u16) }
     pub fn set(&mut self, setting: PPUSettings, val: u8) { self.bus.borrow_mut().write_byte(setting as u16, val); }
@@ -55,155 +81,321 @@ impl PPU {
         // use PPUModes::*;
         use PPUSettings::*;
 
-        self.cycle_count += cycles;
+        // A switched off LCD sits at the top of the screen in HBlank
+        if self.get(LCDC) & 0x80 == 0 {
+            self.cycle_count = 0;
+            self.window_line = 0;
+            let mut bus = self.bus.borrow_mut();
+            bus.memory[LY as usize]   = 0;
+            bus.memory[STAT as usize] &= !0x03;
+            return;
+        }
 
-        while self.cycle_count >= 456 {
-            self.cycle_count -= 456;
-            let ly = self.get(LY);
-            
-                 if (ly + 1) % 154 <  144 { self.render_scanline(); } 
-            else if (ly + 1) % 154 == 144 { self.bus.borrow_mut().inf |= 0x01; }
+        // Stop at every mode change on the way, so a long stall (STOP, HDMA) still renders each
+        // line it covers and gives each of them its HBlank
+        let mut left = cycles;
+        loop {
+            self.update_mode();
+            if left == 0 { break; }
+
+            let boundary = if self.cycle_count < 80 { 80 } else if self.cycle_count < 252 { 252 } else { 456 };
+            let slice    = left.min(boundary - self.cycle_count);
+            self.cycle_count += slice;
+            left -= slice;
+
+            // Every visible line reaches HBlank exactly once: finish it and move one HDMA block
+            if self.cycle_count == 252 && self.get(LY) < 144 {
+                self.render_scanline();
+                self.bus.borrow_mut().hblank();
+            }
+            if self.cycle_count < 456 { continue; }
 
+            self.cycle_count = 0;
+            let ly = (self.get(LY) + 1) % 154;
             self.set(LY, ly);
+
+            if ly == 144 {
+                self.window_line = 0;
+                self.bus.borrow_mut().inf |= 0x01;
+                self.bus.borrow_mut().apply_cheats();
+                self.sgb_transfer();
+                self.draw_buffer();
+            }
         }
     }
 
+    // Keep STAT's mode bits current; the bus also needs the mode for the DMG OAM bug
+    fn update_mode(&mut self) {
+        use PPUSettings::*;
+
+        let ly   = self.get(LY);
+        let mode = if ly >= 144              { 1 }
+              else if self.cycle_count < 80  { 2 }
+              else if self.cycle_count < 252 { 3 }
+              else                           { 0 };
+
+        let stat = self.get(STAT);
+        let mut bus = self.bus.borrow_mut();
+        bus.memory[STAT as usize] = (stat & !0x03) | mode;
+        bus.oam_scan_row = (self.cycle_count / 4).min(19) as u8;
+    }
+
     pub fn render_scanline(&mut self) {
         self.render_background();
         self.render_window();
         self.render_sprites();
-
-        self.draw_buffer();
     }
 
     fn render_background(&mut self) {
         use PPUSettings::*;
 
         let lcdc = self.get(LCDC);
+        let cgb  = self.bus.borrow().cgb_mode;
+        let ly   = self.get(LY);
+
+        self.bg_line = [(0, false); 160];
 
-        if lcdc & 0x01 == 0 { return; }
+        // On CGB, LCDC.0 only takes away BG priority; on DMG it blanks the background
+        if lcdc & 0x01 == 0 && !cgb {
+            for x in 0u8..160 { self.buffer[ly as usize][x as usize] = self.shade_color(BGP, 0, x); }
+            return;
+        }
 
+        // The map is 256x256; u8 arithmetic takes care of the wrap around
         let tile_map_area:u16  = if lcdc & 0x08 == 0 { 0x1800 } else { 0x1C00 };
-        let tile_data_area:u16 = if lcdc & 0x10 == 0 { 0x0000 } else { 0x0800 };
-        let y = self.get(LY).wrapping_add(self.get(LYC));
+        let y = ly.wrapping_add(self.get(SCY));
+        let scx = self.get(SCX);
         let tile_row = (y / 8) as u16 * 32;
 
         for x in 0u8..160 {
-            let scrolled_x = x.wrapping_add(self.get(SCX));
+            let scrolled_x = x.wrapping_add(scx);
             let tile_column = (scrolled_x / 8) as u16;
-            let tile_index = self.read_vram(tile_map_area + tile_row + tile_column);
-            let tile_address = tile_data_area + (tile_index as u16 * 16);
-            let line = self.read_vram(tile_address + ((y % 8) * 2) as u16);
+            let map_address = tile_map_area + tile_row + tile_column;
+            let tile_index = self.read_vram(0, map_address);
+            let attributes = if cgb { self.read_vram(1, map_address) } else { 0 };
 
-            let color_bit = 1 << (7 - (scrolled_x % 8));
-            let color_id = if line & color_bit != 0 { 1 } else { 0 };
-            let color = self.get_color(self.get(BGP), color_id);
-            self.buffer[self.get(LY) as usize][x as usize] = color;
+            let tile_address = self.bg_tile_address(lcdc, tile_index);
+            let tile_y = if attributes & 0x40 == 0 { y % 8 } else { 7 - (y % 8) };
+            let row = self.fetch_tile_row(((attributes >> 3) & 0x01) as usize, tile_address, tile_y, attributes & 0x20 != 0);
+            let color_id = row[(scrolled_x % 8) as usize];
+            let color = if cgb { self.get_cgb_color(false, attributes & 0x07, color_id) } else { self.get_color(BGP, color_id, x) };
+
+            self.bg_line[x as usize] = (color_id, attributes & 0x80 != 0);
+            self.buffer[ly as usize][x as usize] = color;
         }
     }
     fn render_window(&mut self) {
         use PPUSettings::*;
         let lcdc = self.get(LCDC);
+        let cgb  = self.bus.borrow().cgb_mode;
 
-        if lcdc & 0x20 == 0 { return; }
+        if lcdc & 0x20 == 0 || (lcdc & 0x01 == 0 && !cgb) { return; }
 
+        // WX is offset by 7; anything past 166 puts the window off screen
         let ly = self.get(LY);
         let wy = self.get(WY);
-        let window_y = wy.wrapping_add(ly);
-        if ly < wy || window_y >= 144 { return; }
+        let wx = self.get(WX) as i16 - 7;
+        if ly < wy || wx >= 160 { return; }
 
+        // The window keeps its own line counter, so lines it skipped don't count
         let tile_map_area = if lcdc & 0x40 == 0 { 0x1800 } else { 0x1C00 };
-        let tile_data_area = if lcdc & 0x10 == 0 { 0x0000 } else { 0x0800 };
-        let y = ly.wrapping_sub(wy);
+        let y = self.window_line;
         let tile_row = (y / 8) as u16 * 32;
+        self.window_line += 1;
 
         for x in 0u8..160 {
-            let window_x = x.wrapping_sub(self.get(WX).wrapping_sub(7));
-            if window_x >= 160 { continue; }
+            if (x as i16) < wx { continue; }
+            let window_x = (x as i16 - wx) as u8;
 
             let tile_column = (window_x / 8) as u16;
-            let tile_index = self.read_vram(tile_map_area + tile_row + tile_column);
-            let tile_address = tile_data_area + (tile_index as u16 * 16);
-            let line = self.read_vram(tile_address + ((y % 8) * 2) as u16);
+            let map_address = tile_map_area + tile_row + tile_column;
+            let tile_index = self.read_vram(0, map_address);
+            let attributes = if cgb { self.read_vram(1, map_address) } else { 0 };
+
+            let tile_address = self.bg_tile_address(lcdc, tile_index);
+            let tile_y = if attributes & 0x40 == 0 { y % 8 } else { 7 - (y % 8) };
+            let row = self.fetch_tile_row(((attributes >> 3) & 0x01) as usize, tile_address, tile_y, attributes & 0x20 != 0);
+            let color_id = row[(window_x % 8) as usize];
+            let color = if cgb { self.get_cgb_color(false, attributes & 0x07, color_id) } else { self.get_color(BGP, color_id, x) };
 
-            let color_bit = 1 << (7 - (window_x % 8));
-            let color_id = if line & color_bit != 0 { 1 } else { 0 };
-            let color = self.get_color(self.get(BGP), color_id);
+            self.bg_line[x as usize] = (color_id, attributes & 0x80 != 0);
             self.buffer[ly as usize][x as usize] = color;
         }
     }
 
+    // OAM scan: the first 10 sprites (in OAM order) that overlap this line; OAM Y is offset by
+    // 16 and X by 8, so sprites can sit partly off screen
+    fn scan_oam(&self, ly: u8, sprite_height: u8) -> Vec<u8> {
+        let line = ly as u16 + 16;
+
+        (0u8..40).filter(|&sprite| {
+            let y = self.read_oam(sprite as u16 * 4) as u16;
+            line >= y && line < y + sprite_height as u16
+        }).take(10).collect()
+    }
+
     fn render_sprites(&mut self) {
         use PPUSettings::*;
         let lcdc = self.get(LCDC);
+        let cgb  = self.bus.borrow().cgb_mode;
+        let ly   = self.get(LY);
 
         if lcdc & 0x02 == 0 { return; }
 
         let sprite_height = if lcdc & 0x04 == 0 { 8 } else { 16 };
+        let mut sprites = self.scan_oam(ly, sprite_height);
 
-        for sprite in (0..40).rev() {
-            let index = (sprite * 4) as u16;
-            let y = self.read_oam(index) - 16;
-            let x = self.read_oam(index + 1) - 8;
-            let tile_index = self.read_oam(index + 2);
+        // CGB mode goes by OAM order alone; DMG lets the lowest X win and OAM order break ties
+        if !cgb { sprites.sort_by_key(|&sprite| (self.read_oam(sprite as u16 * 4 + 1), sprite)); }
+
+        // Highest priority first: a pixel belongs to the first sprite that isn't transparent there
+        let mut line = [None; 160];
+        for &sprite in &sprites {
+            let index = sprite as u16 * 4;
+            let y = self.read_oam(index) as i16 - 16;
+            let x = self.read_oam(index + 1) as i16 - 8;
             let attributes = self.read_oam(index + 3);
 
-            if self.get(LY) < y as u8 || self.get(LY) >= (y + sprite_height) as u8 { continue; }
+            // 8x16 sprites pair an even tile with the odd one after it
+            let tile_index = if sprite_height == 16 { self.read_oam(index + 2) & 0xFE } else { self.read_oam(index + 2) };
+
+            let row = (ly as i16 - y) as u8;
+            let row = if attributes & 0x40 == 0 { row } else { sprite_height - 1 - row };
+
+            // CGB sprites can take their tile from VRAM bank 1
+            let bank = if cgb { ((attributes >> 3) & 0x01) as usize } else { 0 };
+            let tile_address:u16 = tile_index as u16 * 16;
+            let pixels = self.fetch_tile_row(bank, tile_address, row, attributes & 0x20 != 0);
+
+            for (tile_x, &color_id) in pixels.iter().enumerate() {
+                let pixel_x = x + tile_x as i16;
+                if color_id == 0 || !(0..160).contains(&pixel_x) { continue; }
+
+                let slot = &mut line[pixel_x as usize];
+                if slot.is_none() { *slot = Some((color_id, attributes)); }
+            }
+        }
+
+        for (pixel_x, pixel) in line.iter().enumerate() {
+            let Some((color_id, attributes)) = *pixel else { continue; };
+
+            // BG colors 1-3 cover sprites with the BG-over-OBJ bit (or CGB tiles with the
+            // priority bit); with LCDC.0 cleared on CGB, sprites always win
+            let (bg_color, bg_priority) = self.bg_line[pixel_x];
+            let master = !cgb || lcdc & 0x01 != 0;
+            if master && bg_color != 0 && (attributes & 0x80 != 0 || bg_priority) { continue; }
 
-            let line = if attributes & 0x40 == 0 {
-                self.get(LY) - y as u8
+            let color = if cgb {
+                self.get_cgb_color(true, attributes & 0x07, color_id)
             } else {
-                (sprite_height as u8) - 1 - (self.get(LY) - y as u8)
+                let palette = if attributes & 0x10 == 0 { OGP0 } else { OGP1 };
+                self.get_color(palette, color_id, pixel_x as u8)
             };
+            self.buffer[ly as usize][pixel_x] = color;
+        }
+    }
+
+    // LCDC.4 set: tiles 0-255 from 0x8000. Cleared: signed indices around 0x9000, so 128-255
+    // land in 0x8800-0x8FFF. Offsets are into VRAM.
+    fn bg_tile_address(&self, lcdc: u8, tile_index: u8) -> u16 {
+        if lcdc & 0x10 != 0 { tile_index as u16 * 16 }
+        else                { (0x1000 + tile_index as i8 as i16 * 16) as u16 }
+    }
+
+    // One row of a tile as 2-bit color ids, leftmost pixel first. Each row is two bytes: the low
+    // bit of every pixel in the first, the high bit in the second.
+    fn fetch_tile_row(&self, bank: usize, tile_address: u16, row: u8, x_flip: bool) -> [u8; 8] {
+        let low  = self.read_vram(bank, tile_address + row as u16 * 2);
+        let high = self.read_vram(bank, tile_address + row as u16 * 2 + 1);
 
-            let tile_address:u16 = 0x0000 + (tile_index as u16 * 16) + (line as u16 * 2);
-            let tile_data = self.read_vram(tile_address);
+        let mut pixels = [0; 8];
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            let bit = if x_flip { i } else { 7 - i };
+            *pixel = ((high >> bit) & 0x01) << 1 | ((low >> bit) & 0x01);
+        }
 
-            for tile_x in 0u8..8 {
-                let color_bit = 1 << (7 - tile_x);
-                let color_id = if tile_data & color_bit != 0 { 1 } else { 0 };
+        pixels
+    }
 
-                if color_id == 0 { continue; }
+    // SGB *_TRN data is whatever the game put on screen: the first 256 tiles of the BG map, row by row
+    fn sgb_transfer(&mut self) {
+        use PPUSettings::*;
 
-                let pixel_x = if attributes & 0x20 == 0 {
-                    x + tile_x
-                } else {
-                    x + (7 - tile_x) 
-                };
+        if !self.bus.borrow().sgb.as_ref().is_some_and(|sgb| sgb.has_pending_transfer()) { return; }
 
-                if pixel_x < 0 || pixel_x >= 160 { continue; }
+        let lcdc = self.get(LCDC);
+        let tile_map_area: u16 = if lcdc & 0x08 == 0 { 0x1800 } else { 0x1C00 };
+
+        let mut data = Vec::with_capacity(0x1000);
+        for i in 0u16..256 {
+            let tile_index = self.read_vram(0, tile_map_area + (i / 20) * 32 + i % 20);
+            let tile_address = self.bg_tile_address(lcdc, tile_index);
+
+            for byte in 0..16 { data.push(self.read_vram(0, tile_address + byte)); }
+        }
+
+        if let Some(sgb) = self.bus.borrow_mut().sgb.as_mut() { sgb.transfer(&data); }
+    }
 
-                let palette = if attributes & 0x10 == 0 {
-                    self.get(OGP0)
-                } else {
-                    self.get(OGP1)
+    fn draw_buffer(&mut self) {
+        let mut frame = std::mem::take(&mut self.frame);
+        {
+            let bus = self.bus.borrow();
+            let sgb = bus.sgb.as_ref();
+            let (width, height) = if sgb.is_some() { (SCREEN_WIDTH, SCREEN_HEIGHT) } else { (160, 144) };
+            frame.resize(width * height, 0);
+
+            for (i, pixel) in frame.iter_mut().enumerate() {
+                let (x, y) = (i % width, i / width);
+                let color = match sgb {
+                    Some(sgb) => match self.sgb_pixel(sgb, x, y) { Some(color) => color, None => continue },
+                    None      => self.buffer[y][x],
                 };
-                let color = self.get_color(palette, color_id);
-                self.buffer[self.get(LY) as usize][pixel_x as usize] = color;
+
+                *pixel = color;
             }
+
+            if let Some(sink) = self.sink.as_mut() { sink.present(&frame, width, height); }
         }
+        self.frame = frame;
     }
 
-    fn draw_buffer(&mut self) {
-        let mut pxs = self.pixels.borrow_mut();
-        let frame   = pxs.get_frame();
+    // The 256x224 SGB picture: border on top, the game (or its mask) in the middle, backdrop
+    // color elsewhere. None leaves the pixel alone while the game area is frozen.
+    fn sgb_pixel(&self, sgb: &Sgb, x: usize, y: usize) -> Option<u32> {
+        if let Some(color) = sgb.border_color(x, y) { return Some(color); }
+
+        let (gx, gy) = (x.wrapping_sub(GAME_X), y.wrapping_sub(GAME_Y));
+        if gx >= 160 || gy >= 144 { return Some(sgb.backdrop()); }
 
-        for (i, pixel) in frame.chunks_exact_mut(4).enumerate() {
-            let x = (i % 160) as usize;
-            let y = (i / 160) as usize;
-            let color = self.buffer[y][x];
-            
-            pixel[0] = (color >> 16) as u8;
-            pixel[1] = (color >> 8)  as u8;
-            pixel[2] = (color)       as u8;
-            pixel[3] = 0xFF;
+        match sgb.mask {
+            Mask::Freeze => None,
+            _ => Some(sgb.masked_color().unwrap_or(self.buffer[gy][gx])),
         }
+    }
 
-        self.pixels.borrow().render().expect("Failed to render frame");
+    // On a Super Game Boy the shade gets colored by the SGB palette of the cell it lands in
+    fn get_color(&self, palette: PPUSettings, color_id: u8, x: u8) -> u32 {
+        let shade = (self.get(palette) >> (color_id * 2)) & 0x03;
+        self.shade_color(palette, shade, x)
     }
 
-    fn get_color(&self, palette: u8, color_id: u8) -> u32 {
-        match (palette >> (color_id * 2)) & 0x03 {
+    // DMG games on CGB hardware get colorized per palette register, the way the CGB boot ROM sets it up
+    fn shade_color(&self, palette: PPUSettings, shade: u8, x: u8) -> u32 {
+        let bus = self.bus.borrow();
+
+        if let Some(sgb) = bus.sgb.as_ref() {
+            return sgb.game_color(x as usize, self.get(PPUSettings::LY) as usize, shade);
+        }
+        if let Some(colors) = self.dmg_colors.filter(|_| bus.model.is_cgb() && !bus.cgb_mode) {
+            return match palette {
+                PPUSettings::OGP0 => colors.obj0,
+                PPUSettings::OGP1 => colors.obj1,
+                _                 => colors.bg,
+            }[shade as usize];
+        }
+
+        match shade {
             0 => 0xFFFFFF, 
             1 => 0xAAAAAA,
             2 => 0x555555,
@@ -211,4 +403,139 @@ impl PPU {
             _ => 0x069420,
         }
     }
-}
\ No newline at end of file
+
+    // CGB palette RAM holds 8 palettes of 4 little-endian RGB555 colors
+    fn get_cgb_color(&self, obj: bool, palette: u8, color_id: u8) -> u32 {
+        let bus   = self.bus.borrow();
+        let ram   = if obj { &bus.obj_palettes } else { &bus.bg_palettes };
+        let index = (palette as usize * 4 + color_id as usize) * 2;
+
+        rgb555(u16::from_le_bytes([ram[index], ram[index + 1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::tests::bus;
+    use crate::model::Model;
+
+    fn ppu(model: Model) -> PPU {
+        PPU::new(Rc::new(RefCell::new(bus(model))))
+    }
+
+    #[test]
+    fn long_stalls_render_every_line() {
+        let mut ppu = ppu(Model::Dmg);
+        ppu.update(456 * 10 + 100);
+
+        assert_eq!(ppu.get(PPUSettings::LY), 10);
+        assert_eq!(ppu.get(PPUSettings::STAT) & 0x03, 3);
+        assert!(ppu.buffer[..10].iter().all(|line| line.iter().all(|&pixel| pixel == 0xFFFFFF)));
+        assert!(ppu.buffer[10].iter().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn long_stalls_reach_vblank() {
+        let mut ppu = ppu(Model::Dmg);
+        for _ in 0..2 { ppu.update(456 * 72); }
+
+        assert_eq!(ppu.get(PPUSettings::LY), 144);
+        assert_eq!(ppu.get(PPUSettings::STAT) & 0x03, 1);
+        assert_eq!(ppu.bus.borrow().inf & 0x01, 0x01);
+    }
+
+    #[test]
+    fn hblank_dma_moves_one_block_per_line() {
+        let mut ppu = ppu(Model::Cgb);
+        {
+            let mut bus = ppu.bus.borrow_mut();
+            for i in 0..0x80 { bus.write_byte(0xC000 + i, i as u8 + 1); }
+            for (reg, val) in [(0xFF51, 0xC0), (0xFF52, 0x00), (0xFF53, 0x00), (0xFF54, 0x00), (0xFF55, 0x87)] {
+                bus.write_byte(reg, val);
+            }
+        }
+
+        // Three and a half lines in one go: three HBlanks
+        ppu.update(456 * 3 + 200);
+        assert_eq!(ppu.read_vram(0, 0x2F), 0x30);
+        assert_eq!(ppu.read_vram(0, 0x30), 0x00);
+        assert_eq!(ppu.bus.borrow().read_byte(0xFF55), 0x04);
+
+        ppu.update(456 * 5);
+        assert_eq!(ppu.read_vram(0, 0x7F), 0x80);
+        assert_eq!(ppu.bus.borrow().read_byte(0xFF55), 0xFF);
+    }
+
+    #[test]
+    fn decodes_tile_rows() {
+        let mut ppu = ppu(Model::Dmg);
+        ppu.write_vram(0, 0x0012, 0b1010_0001);
+        ppu.write_vram(0, 0x0013, 0b1100_0001);
+
+        assert_eq!(ppu.fetch_tile_row(0, 0x0010, 1, false), [3, 2, 1, 0, 0, 0, 0, 3]);
+        assert_eq!(ppu.fetch_tile_row(0, 0x0010, 1, true),  [3, 0, 0, 0, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn signed_tile_addressing() {
+        let ppu = ppu(Model::Dmg);
+        assert_eq!(ppu.bg_tile_address(0x91, 0x01), 0x0010);
+        assert_eq!(ppu.bg_tile_address(0x81, 0x01), 0x1010);
+        assert_eq!(ppu.bg_tile_address(0x81, 0x80), 0x0800);
+        assert_eq!(ppu.bg_tile_address(0x81, 0xFF), 0x0FF0);
+    }
+
+    #[test]
+    fn oam_scan_keeps_the_first_ten_sprites() {
+        let mut ppu = ppu(Model::Dmg);
+        for sprite in 0..12 { ppu.write_oam(sprite * 4, 16); }
+        ppu.write_oam(2 * 4, 40);   // Off the line
+        ppu.write_oam(3 * 4, 8);    // Only overlaps line 0 as an 8x16 sprite
+
+        assert_eq!(ppu.scan_oam(0, 8),  vec![0, 1, 4, 5, 6, 7, 8, 9, 10, 11]);
+        assert_eq!(ppu.scan_oam(0, 16), vec![0, 1, 3, 4, 5, 6, 7, 8, 9, 10]);
+    }
+
+    // Sprite 0 (color 1) at screen X 12 overlaps sprite 1 (color 2) at screen X 8
+    fn overlapping_sprites(model: Model) -> PPU {
+        let mut ppu = ppu(model);
+        ppu.write_vram(0, 0x0010, 0xFF);    // Tile 1, row 0: low bits only
+        ppu.write_vram(0, 0x0021, 0xFF);    // Tile 2, row 0: high bits only
+        for (sprite, (x, tile)) in [(20, 1), (16, 2)].into_iter().enumerate() {
+            let index = sprite as u16 * 4;
+            ppu.write_oam(index, 16);
+            ppu.write_oam(index + 1, x);
+            ppu.write_oam(index + 2, tile);
+            ppu.write_oam(index + 3, 0);
+        }
+        ppu.set(PPUSettings::LCDC, 0x93);
+        ppu.set(PPUSettings::OGP0, 0xE4);
+        ppu
+    }
+
+    #[test]
+    fn dmg_sprites_go_by_x_priority() {
+        let mut ppu = overlapping_sprites(Model::Dmg);
+        ppu.render_sprites();
+
+        assert_eq!(ppu.buffer[0][8],  0x555555);
+        assert_eq!(ppu.buffer[0][12], 0x555555);
+        assert_eq!(ppu.buffer[0][16], 0xAAAAAA);
+    }
+
+    #[test]
+    fn cgb_sprites_go_by_oam_order() {
+        let mut ppu = overlapping_sprites(Model::Cgb);
+        {
+            let mut bus = ppu.bus.borrow_mut();
+            bus.cgb_mode = true;
+            bus.obj_palettes[2..6].copy_from_slice(&[0x1F, 0x00, 0x00, 0x7C]);
+        }
+        ppu.render_sprites();
+
+        assert_eq!(ppu.buffer[0][8],  rgb555(0x7C00));
+        assert_eq!(ppu.buffer[0][12], rgb555(0x001F));
+        assert_eq!(ppu.buffer[0][16], rgb555(0x001F));
+    }
+}
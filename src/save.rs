@@ -0,0 +1,71 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::mbc::rtc::{Rtc, RTC_TRAILER_SIZE, RTC_TRAILER_SIZE_SHORT};
+
+pub(crate) const DEFAULT_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Battery-backed RAM stored next to the ROM as raw bytes, the same layout other emulators use,
+// with the MBC3 clock appended as the common 48-byte trailer
+pub(crate) struct SaveFile {
+    path:       PathBuf,
+    pub dirty:  bool,
+    interval:   Duration,
+    last_flush: Instant,
+}
+
+impl SaveFile {
+    pub fn for_rom(rom_path: &Path) -> Self {
+        SaveFile {
+            path:       rom_path.with_extension("sav"),
+            dirty:      false,
+            interval:   DEFAULT_SAVE_INTERVAL,
+            last_flush: Instant::now(),
+        }
+    }
+
+    pub fn path(&self) -> &Path { &self.path }
+
+    pub fn set_interval(&mut self, interval: Duration) { self.interval = interval; }
+
+    // Missing saves are fine (first boot), short ones fill what they can
+    pub fn load(&self, ram: &mut [u8], rtc: Option<&mut Rtc>) -> io::Result<()> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let len = ram.len().min(data.len());
+        ram[..len].copy_from_slice(&data[..len]);
+
+        if let Some(rtc) = rtc {
+            let trailer = &data[len..];
+            if trailer.len() == RTC_TRAILER_SIZE || trailer.len() == RTC_TRAILER_SIZE_SHORT {
+                rtc.load_trailer(trailer);
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn store(&mut self, ram: &[u8], rtc: Option<&mut Rtc>) -> io::Result<()> {
+        let mut data = ram.to_vec();
+        if let Some(rtc) = rtc { data.extend_from_slice(&rtc.save_trailer()); }
+
+        // Write to a temporary first so a crash mid-write can't eat the old save
+        let tmp = self.path.with_extension("sav.tmp");
+        fs::write(&tmp, &data)?;
+        fs::rename(&tmp, &self.path)?;
+
+        self.dirty      = false;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+
+    pub fn due(&self) -> bool {
+        self.dirty && self.last_flush.elapsed() >= self.interval
+    }
+}
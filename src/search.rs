@@ -0,0 +1,237 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::memory::MemoryBus;
+
+const WRAM_START:  u16 = 0xC000;
+const WRAM_SWAP:   u16 = 0xD000;
+const WRAM_BANK:   usize = 0x1000;
+const HRAM_START:  u16 = 0xFF80;
+const HRAM_END:    u16 = 0xFFFE;
+const CRAM_START:  u16 = 0xA000;
+const CRAM_BANK:   usize = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region { CartRam, WorkRam, HighRam }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Width { Byte, Word }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal, Changed, Increased, Decreased,
+    Value(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub region:   Region,
+    pub offset:   usize,    // Into the region; cart RAM and WRAM offsets span every bank
+    pub banked:   bool,     // Cart RAM always is, WRAM only on CGB
+    pub value:    i32,
+    pub previous: i32,
+}
+
+impl Candidate {
+    // Where the game sees it, plus the cart RAM bank it lives in
+    pub fn address(&self) -> (u16, Option<usize>) {
+        match self.region {
+            Region::CartRam => (CRAM_START + (self.offset % CRAM_BANK) as u16, Some(self.offset / CRAM_BANK)),
+            Region::WorkRam if !self.banked || self.offset < WRAM_BANK => (WRAM_START + self.offset as u16, None),
+            Region::WorkRam => (WRAM_SWAP + (self.offset % WRAM_BANK) as u16, Some(self.offset / WRAM_BANK)),
+            Region::HighRam => (HRAM_START + self.offset as u16, None),
+        }
+    }
+}
+
+// Cheat finder over the writable RAM the game keeps its state in. Start it, let the game run,
+// then narrow the candidates down with comparisons against the previous pass.
+pub struct RamSearch {
+    width:      Width,
+    signed:     bool,
+    candidates: Vec<Candidate>,
+}
+
+impl RamSearch {
+    pub(crate) fn start(bus: &MemoryBus, width: Width, signed: bool) -> Self {
+        let mut search = RamSearch { width: width, signed: signed, candidates: Vec::new() };
+
+        for region in [Region::CartRam, Region::WorkRam, Region::HighRam] {
+            let bytes  = RamSearch::region_bytes(bus, region);
+            let step   = match width { Width::Byte => 1, Width::Word => 2 };
+            let banked = region == Region::CartRam || (region == Region::WorkRam && bus.model.is_cgb());
+
+            for offset in 0..bytes.len().saturating_sub(step - 1) {
+                if width == Width::Word && banked && RamSearch::ends_bank(region, offset) { continue; }
+
+                let value = search.decode(&bytes, offset);
+                search.candidates.push(Candidate { region, offset, banked, value, previous: value });
+            }
+        }
+
+        search
+    }
+
+    pub fn candidates(&self) -> &[Candidate] { &self.candidates }
+
+    pub(crate) fn filter(&mut self, bus: &MemoryBus, cmp: Comparison) -> usize {
+        let regions = [Region::CartRam, Region::WorkRam, Region::HighRam].map(|r| RamSearch::region_bytes(bus, r));
+        let (width, signed) = (self.width, self.signed);
+
+        self.candidates.retain_mut(|c| {
+            let bytes = &regions[c.region as usize];
+            let Some(value) = RamSearch::decode_with(width, signed, bytes, c.offset) else { return false; };
+
+            let keep = match cmp {
+                Comparison::Equal     => value == c.value,
+                Comparison::Changed   => value != c.value,
+                Comparison::Increased => value >  c.value,
+                Comparison::Decreased => value <  c.value,
+                Comparison::Value(v)  => value == v,
+            };
+
+            c.previous = c.value;
+            c.value    = value;
+            keep
+        });
+
+        self.candidates.len()
+    }
+
+    // Tab separated: region, bank, address, width, signedness, current value
+    pub fn write_watch_list(&self, path: &Path) -> io::Result<()> {
+        let width  = match self.width { Width::Byte => "u8", Width::Word => "u16" };
+        let signed = if self.signed { "signed" } else { "unsigned" };
+
+        let mut out = String::from("# region\tbank\taddress\twidth\tsign\tvalue\n");
+        for c in &self.candidates {
+            let (addr, bank) = c.address();
+            out += &format!("{:?}\t{}\t{:04X}\t{}\t{}\t{}\n",
+                c.region, bank.map_or("-".to_string(), |b| b.to_string()), addr, width, signed, c.value);
+        }
+
+        fs::write(path, out)
+    }
+
+    fn region_bytes(bus: &MemoryBus, region: Region) -> Vec<u8> {
+        match region {
+            Region::CartRam => bus.rom.ram().to_vec(),
+            Region::WorkRam => bus.wram[..if bus.model.is_cgb() { 8 } else { 2 }].concat(),
+            Region::HighRam => (HRAM_START..=HRAM_END).map(|a| bus.read_byte(a)).collect(),
+        }
+    }
+
+    // The next byte lives in a bank that's never mapped right after this one, so no word starts
+    // here. WRAM bank 0 is always followed by whatever bank sits at 0xD000.
+    fn ends_bank(region: Region, offset: usize) -> bool {
+        match region {
+            Region::CartRam => offset % CRAM_BANK == CRAM_BANK - 1,
+            Region::WorkRam => offset >= WRAM_BANK && offset % WRAM_BANK == WRAM_BANK - 1,
+            Region::HighRam => false,
+        }
+    }
+
+    fn decode(&self, bytes: &[u8], offset: usize) -> i32 {
+        RamSearch::decode_with(self.width, self.signed, bytes, offset).unwrap_or(0)
+    }
+
+    // Words are little-endian like everything else on the CPU
+    fn decode_with(width: Width, signed: bool, bytes: &[u8], offset: usize) -> Option<i32> {
+        Some(match (width, signed) {
+            (Width::Byte, false) => *bytes.get(offset)? as i32,
+            (Width::Byte, true)  => *bytes.get(offset)? as i8 as i32,
+            (Width::Word, false) => u16::from_le_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]) as i32,
+            (Width::Word, true)  => i16::from_le_bytes([*bytes.get(offset)?, *bytes.get(offset + 1)?]) as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::{CartridgeHeader, ROM};
+    use crate::model::Model;
+
+    // MBC1 with four banks of cart RAM
+    fn bus() -> MemoryBus {
+        let mut rom = vec![0; 0x8000];
+        rom[0x147] = 0x03;
+        rom[0x149] = 0x03;
+        rom[0x14D] = CartridgeHeader::compute_header_checksum(&rom);
+        MemoryBus::new(ROM::from_bytes(rom).unwrap())
+    }
+
+    #[test]
+    fn covers_cart_ram_wram_and_hram() {
+        assert_eq!(RamSearch::start(&bus(), Width::Byte, false).candidates().len(), 4 * CRAM_BANK + 0x2000 + 127);
+        assert_eq!(RamSearch::start(&bus(), Width::Word, false).candidates().len(), 4 * CRAM_BANK - 4 + 0x2000 - 1 + 126);
+    }
+
+    #[test]
+    fn narrows_down_candidates() {
+        let mut bus    = bus();
+        let mut search = RamSearch::start(&bus, Width::Byte, false);
+
+        // Cart RAM bank 2, through MBC1's RAM banking mode
+        bus.write_byte(0x0000, 0x0A);
+        bus.write_byte(0x6000, 0x01);
+        bus.write_byte(0x4000, 0x02);
+        bus.write_byte(0xA010, 7);
+        bus.write_byte(0xFF90, 3);
+        assert_eq!(search.filter(&bus, Comparison::Increased), 2);
+
+        let found: Vec<_> = search.candidates().iter().map(|c| c.address()).collect();
+        assert_eq!(found, vec![(0xA010, Some(2)), (0xFF90, None)]);
+
+        bus.write_byte(0xFF90, 2);
+        assert_eq!(search.filter(&bus, Comparison::Decreased), 1);
+        assert_eq!(search.candidates()[0].previous, 3);
+        assert_eq!(search.candidates()[0].value, 2);
+
+        assert_eq!(search.filter(&bus, Comparison::Value(7)), 0);
+    }
+
+    #[test]
+    fn decodes_signed_little_endian_words() {
+        let mut bus = bus();
+        for (addr, val) in [(0xC000, 0xFF), (0xC001, 0xFF), (0xC002, 0x00)] { bus.write_byte(addr, val); }
+
+        let mut search = RamSearch::start(&bus, Width::Word, true);
+        assert_eq!(search.filter(&bus, Comparison::Value(-1)), 1);
+        assert_eq!(search.candidates()[0].address(), (0xC000, None));
+
+        let mut search = RamSearch::start(&bus, Width::Word, false);
+        assert_eq!(search.filter(&bus, Comparison::Value(0xFF)), 1);
+        assert_eq!(search.candidates()[0].address(), (0xC001, None));
+    }
+
+    #[test]
+    fn covers_every_cgb_wram_bank() {
+        let mut bus = bus();
+        bus.set_model(Model::Cgb);
+        assert_eq!(RamSearch::start(&bus, Width::Byte, false).candidates().len(), 4 * CRAM_BANK + 8 * WRAM_BANK + 127);
+        assert_eq!(RamSearch::start(&bus, Width::Word, false).candidates().len(), 4 * CRAM_BANK - 4 + 8 * WRAM_BANK - 7 + 126);
+
+        // SVBK picks the bank behind 0xD000
+        let mut search = RamSearch::start(&bus, Width::Byte, false);
+        bus.write_byte(0xFF70, 0x05);
+        bus.write_byte(0xD020, 7);
+        assert_eq!(search.filter(&bus, Comparison::Value(7)), 1);
+        assert_eq!(search.candidates()[0].address(), (0xD020, Some(5)));
+    }
+
+    #[test]
+    fn dmg_wram_is_not_banked() {
+        let mut bus    = bus();
+        let mut search = RamSearch::start(&bus, Width::Word, false);
+
+        // 0xCFFF-0xD000 is one word on DMG
+        bus.write_byte(0xD020, 7);
+        bus.write_byte(0xD000, 1);
+        assert_eq!(search.filter(&bus, Comparison::Changed), 4);
+
+        let found: Vec<_> = search.candidates().iter().map(|c| c.address()).collect();
+        assert_eq!(found, vec![(0xCFFF, None), (0xD000, None), (0xD01F, None), (0xD020, None)]);
+    }
+}
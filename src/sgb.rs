@@ -0,0 +1,446 @@
+use crate::utils::rgb555;
+
+// Super Game Boy. The cart talks to the SNES side by pulsing P14/P15 in the joypad register:
+// a reset pulse (both low), then 128 bits LSB first (P14 low = 0, P15 low = 1, both high in
+// between) and a 0 stop bit make up one 16 byte packet.
+
+pub const SCREEN_WIDTH:  usize = 256;
+pub const SCREEN_HEIGHT: usize = 224;
+pub const GAME_X:        usize = 48;    // Where the 160x144 game area sits inside the border
+pub const GAME_Y:        usize = 40;
+
+const PACKET_SIZE:   usize = 16;
+const PACKET_BITS:   usize = PACKET_SIZE * 8;
+const TRANSFER_SIZE: usize = 0x1000;
+const ATTR_FILE_SIZE: usize = 90;       // 20x18 cells at 2 bits each
+const ATTR_FILES:    usize = 45;
+
+const COLUMNS: usize = 20;
+const ROWS:    usize = 18;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mask { Off, Freeze, Black, Color0 }
+
+// *_TRN commands copy 4KB out of what's on screen on the next frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Transfer { Palettes, Tiles(usize), Border, Attributes }
+
+pub struct Sgb {
+    receiving: bool,
+    bits:      usize,
+    packet:    [u8; PACKET_SIZE],
+    lines:     u8,                          // Last P14/P15 state written
+    command:   Vec<u8>,                     // Packets of a multi-packet command collected so far
+
+    palettes:        [[u16; 4]; 4],
+    system_palettes: Vec<[u16; 4]>,         // 512 palettes sent with PAL_TRN
+    attributes:      [[u8; COLUMNS]; ROWS], // Palette of each 8x8 cell of the game area
+    attr_files:      Vec<[[u8; COLUMNS]; ROWS]>,
+    pub mask:        Mask,
+
+    border_tiles:    Vec<u8>,               // 256 SNES 4bpp tiles
+    border_map:      Vec<u16>,              // 32x28 entries: tile, palette, flips
+    border_palettes: [[u16; 16]; 4],        // SNES palettes 4-7
+    pending:         Option<Transfer>,
+
+    players: u8,
+    player:  u8,                            // Joypad reported while MLT_REQ is active
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Sgb {
+            receiving: false, bits: 0, packet: [0; PACKET_SIZE], lines: 0x30, command: Vec::new(),
+
+            palettes:        [[0x7FFF, 0x56B5, 0x294A, 0x0000]; 4],
+            system_palettes: Vec::new(),
+            attributes:      [[0; COLUMNS]; ROWS],
+            attr_files:      Vec::new(),
+            mask:            Mask::Off,
+
+            border_tiles:    vec![0; TRANSFER_SIZE * 2],
+            border_map:      vec![0; 32 * 28],
+            border_palettes: [[0; 16]; 4],
+            pending:         None,
+
+            players: 1, player: 0,
+        }
+    }
+
+    pub fn players(&self) -> u8 { self.players }
+
+    pub fn current_player(&self) -> u8 { self.player }
+
+    // Every write to 0xFF00 goes through here
+    pub fn write_joypad(&mut self, val: u8) {
+        let lines = val & 0x30;
+        let prev  = std::mem::replace(&mut self.lines, lines);
+        if lines == prev { return; }
+
+        // With multiplayer on, releasing P15 moves on to the next joypad
+        if self.players > 1 && lines == 0x30 && prev & 0x20 == 0 {
+            self.player = (self.player + 1) % self.players;
+        }
+
+        match lines {
+            0x00 => {
+                self.receiving = true;
+                self.bits      = 0;
+                self.packet    = [0; PACKET_SIZE];
+            }
+            0x10 | 0x20 if self.receiving && prev == 0x30 => {
+                let bit = lines == 0x10;
+
+                if self.bits == PACKET_BITS {
+                    self.receiving = false;
+                    if !bit { self.receive_packet(); }
+                    return;
+                }
+
+                if bit { self.packet[self.bits / 8] |= 1 << (self.bits % 8); }
+                self.bits += 1;
+            }
+            _ => { }
+        }
+    }
+
+    // The low 3 bits of the first byte say how many packets the command spans
+    fn receive_packet(&mut self) {
+        self.command.extend_from_slice(&self.packet);
+
+        let packets = (self.command[0] & 0x07).max(1) as usize;
+        if self.command.len() >= packets * PACKET_SIZE {
+            let command = std::mem::take(&mut self.command);
+            self.execute(&command);
+        }
+    }
+
+    fn execute(&mut self, cmd: &[u8]) {
+        match cmd[0] >> 3 {
+            0x00 => self.set_palettes(0, 1, cmd),   // PAL01
+            0x01 => self.set_palettes(2, 3, cmd),   // PAL23
+            0x02 => self.set_palettes(0, 3, cmd),   // PAL03
+            0x03 => self.set_palettes(1, 2, cmd),   // PAL12
+            0x04 => self.attr_blk(cmd),
+            0x05 => self.attr_lin(cmd),
+            0x06 => self.attr_div(cmd),
+            0x07 => self.attr_chr(cmd),
+            0x0A => self.pal_set(cmd),
+            0x0B => self.pending = Some(Transfer::Palettes),
+            0x11 => {
+                self.players = match cmd[1] & 0x03 { 1 => 2, 3 => 4, _ => 1 };
+                self.player  = 0;
+            }
+            0x13 => self.pending = Some(Transfer::Tiles((cmd[1] & 0x01) as usize)),
+            0x14 => self.pending = Some(Transfer::Border),
+            0x15 => self.pending = Some(Transfer::Attributes),
+            0x16 => self.attr_set(cmd),
+            0x17 => self.mask = match cmd[1] & 0x03 { 0 => Mask::Off, 1 => Mask::Freeze, 2 => Mask::Black, _ => Mask::Color0 },
+            _ => { } // Sound, SNES code uploads and the like aren't emulated
+        }
+    }
+
+    fn color(bytes: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]]) & 0x7FFF
+    }
+
+    // Color 0 is shared by all four palettes
+    fn set_palettes(&mut self, a: usize, b: usize, cmd: &[u8]) {
+        let color0 = Sgb::color(cmd, 1);
+        for palette in self.palettes.iter_mut() { palette[0] = color0; }
+
+        for i in 0..3 {
+            self.palettes[a][i + 1] = Sgb::color(cmd, 3 + i * 2);
+            self.palettes[b][i + 1] = Sgb::color(cmd, 9 + i * 2);
+        }
+    }
+
+    fn attr_blk(&mut self, cmd: &[u8]) {
+        let count = (cmd[1] & 0x1F) as usize;
+
+        for set in cmd[2..].chunks_exact(6).take(count) {
+            let (inside, border, outside) = (set[1] & 0x03, (set[1] >> 2) & 0x03, (set[1] >> 4) & 0x03);
+            let (x1, y1, x2, y2) = (set[2] & 0x1F, set[3] & 0x1F, set[4] & 0x1F, set[5] & 0x1F);
+
+            // When only one of inside/outside is set, the border goes along with it
+            let (inside, border, outside) = match set[0] & 0x07 {
+                0x01 => (Some(inside), Some(inside), None),
+                0x04 => (None, Some(outside), Some(outside)),
+                ctrl => (
+                    (ctrl & 0x01 != 0).then_some(inside),
+                    (ctrl & 0x02 != 0).then_some(border),
+                    (ctrl & 0x04 != 0).then_some(outside),
+                ),
+            };
+
+            for y in 0..ROWS as u8 {
+                for x in 0..COLUMNS as u8 {
+                    let within = x >= x1 && x <= x2 && y >= y1 && y <= y2;
+                    let edge   = within && (x == x1 || x == x2 || y == y1 || y == y2);
+
+                    let palette = if edge { border } else if within { inside } else { outside };
+                    if let Some(palette) = palette { self.attributes[y as usize][x as usize] = palette; }
+                }
+            }
+        }
+    }
+
+    // Each byte paints a full row (bit 7 set) or column with one palette
+    fn attr_lin(&mut self, cmd: &[u8]) {
+        for &line in cmd[2..].iter().take(cmd[1] as usize) {
+            let (at, palette) = ((line & 0x1F) as usize, (line >> 5) & 0x03);
+
+            if line & 0x80 != 0 {
+                if at < ROWS { self.attributes[at] = [palette; COLUMNS]; }
+            } else if at < COLUMNS {
+                for row in self.attributes.iter_mut() { row[at] = palette; }
+            }
+        }
+    }
+
+    // Split the screen in two at a row or column, with a third palette for the line itself
+    fn attr_div(&mut self, cmd: &[u8]) {
+        let (after, before, on) = (cmd[1] & 0x03, (cmd[1] >> 2) & 0x03, (cmd[1] >> 4) & 0x03);
+        let split = (cmd[2] & 0x1F) as usize;
+        let by_row = cmd[1] & 0x40 != 0;
+
+        for y in 0..ROWS {
+            for x in 0..COLUMNS {
+                let at = if by_row { y } else { x };
+                self.attributes[y][x] = if at < split { before } else if at == split { on } else { after };
+            }
+        }
+    }
+
+    // A run of cells, 2 bits each, going left to right or top to bottom
+    fn attr_chr(&mut self, cmd: &[u8]) {
+        let (mut x, mut y) = (cmd[1] as usize, cmd[2] as usize);
+        let count    = u16::from_le_bytes([cmd[3], cmd[4]]) as usize;
+        let vertical = cmd[5] & 0x01 != 0;
+
+        for i in 0..count {
+            let Some(&byte) = cmd.get(6 + i / 4) else { break; };
+            if x >= COLUMNS || y >= ROWS { break; }
+
+            self.attributes[y][x] = (byte >> (6 - (i % 4) * 2)) & 0x03;
+
+            if vertical { y += 1; if y == ROWS    { y = 0; x += 1; } }
+            else        { x += 1; if x == COLUMNS { x = 0; y += 1; } }
+        }
+    }
+
+    // Pick the four palettes out of the ones sent with PAL_TRN, optionally with an ATTR_TRN file
+    fn pal_set(&mut self, cmd: &[u8]) {
+        for i in 0..4 {
+            let index = (u16::from_le_bytes([cmd[1 + i * 2], cmd[2 + i * 2]]) & 0x01FF) as usize;
+            if let Some(&palette) = self.system_palettes.get(index) { self.palettes[i] = palette; }
+        }
+
+        if cmd[9] & 0x80 != 0 {
+            if let Some(&file) = self.attr_files.get((cmd[9] & 0x3F) as usize) { self.attributes = file; }
+        }
+        if cmd[9] & 0x40 != 0 { self.mask = Mask::Off; }
+    }
+
+    // ATTR_SET: switch to one of the ATTR_TRN files, bit 6 also lifts MASK_EN
+    fn attr_set(&mut self, cmd: &[u8]) {
+        if let Some(&file) = self.attr_files.get((cmd[1] & 0x3F) as usize) { self.attributes = file; }
+        if cmd[1] & 0x40 != 0 { self.mask = Mask::Off; }
+    }
+
+    pub fn has_pending_transfer(&self) -> bool { self.pending.is_some() }
+
+    // Takes the 4KB the game put on screen for the pending *_TRN command
+    pub fn transfer(&mut self, data: &[u8]) {
+        let Some(transfer) = self.pending.take() else { return; };
+        if data.len() < TRANSFER_SIZE { return; }
+
+        match transfer {
+            Transfer::Palettes => {
+                self.system_palettes = data[..TRANSFER_SIZE].chunks_exact(8)
+                    .map(|c| [Sgb::color(c, 0), Sgb::color(c, 2), Sgb::color(c, 4), Sgb::color(c, 6)])
+                    .collect();
+            }
+            Transfer::Tiles(half) => {
+                self.border_tiles[half * TRANSFER_SIZE..(half + 1) * TRANSFER_SIZE].copy_from_slice(&data[..TRANSFER_SIZE]);
+            }
+            Transfer::Border => {
+                for (i, entry) in self.border_map.iter_mut().enumerate() {
+                    *entry = u16::from_le_bytes([data[i * 2], data[i * 2 + 1]]);
+                }
+                for (i, palette) in self.border_palettes.iter_mut().enumerate() {
+                    for (j, color) in palette.iter_mut().enumerate() {
+                        *color = Sgb::color(data, 0x800 + (i * 16 + j) * 2);
+                    }
+                }
+            }
+            Transfer::Attributes => {
+                self.attr_files = data[..ATTR_FILES * ATTR_FILE_SIZE].chunks_exact(ATTR_FILE_SIZE).map(|bytes| {
+                    let mut file = [[0; COLUMNS]; ROWS];
+                    for cell in 0..COLUMNS * ROWS {
+                        file[cell / COLUMNS][cell % COLUMNS] = (bytes[cell / 4] >> (6 - (cell % 4) * 2)) & 0x03;
+                    }
+                    file
+                }).collect();
+            }
+        }
+    }
+
+    // A DMG shade at a spot in the 160x144 game area, colored by the palette covering that cell
+    pub fn game_color(&self, x: usize, y: usize, shade: u8) -> u32 {
+        let palette = self.attributes[(y / 8).min(ROWS - 1)][(x / 8).min(COLUMNS - 1)] as usize;
+        rgb555(self.palettes[palette][shade as usize & 0x03])
+    }
+
+    // What MASK_EN shows instead of the game, None while frozen (keep the last frame)
+    pub fn masked_color(&self) -> Option<u32> {
+        match self.mask {
+            Mask::Black  => Some(0x000000),
+            Mask::Color0 => Some(rgb555(self.palettes[0][0])),
+            _            => None,
+        }
+    }
+
+    pub fn backdrop(&self) -> u32 { rgb555(self.palettes[0][0]) }
+
+    // Border pixel in the 256x224 picture; color 0 is see-through
+    pub fn border_color(&self, x: usize, y: usize) -> Option<u32> {
+        let entry = self.border_map[(y / 8) * 32 + x / 8];
+        let tile  = (entry & 0xFF) as usize * 32;
+        let row   = if entry & 0x8000 != 0 { 7 - y % 8 } else { y % 8 };
+        let bit   = if entry & 0x4000 != 0 { x % 8 } else { 7 - x % 8 };
+
+        // SNES 4bpp: planes 0/1 interleaved in the first 16 bytes, planes 2/3 in the next 16
+        let planes = [tile + row * 2, tile + row * 2 + 1, tile + 16 + row * 2, tile + 17 + row * 2];
+        let index  = planes.iter().enumerate()
+            .fold(0, |acc, (plane, &at)| acc | ((self.border_tiles[at] >> bit) & 0x01) << plane) as usize;
+
+        if index == 0 { return None; }
+
+        let palette = (((entry >> 10) & 0x07) as usize).saturating_sub(4).min(3);
+        Some(rgb555(self.border_palettes[palette][index]))
+    }
+}
+
+impl Default for Sgb {
+    fn default() -> Self { Sgb::new() }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // Bit-bang a packet the way games do: reset pulse, 128 bits, a 0 stop bit
+    pub(crate) fn send_packet(mut write: impl FnMut(u8), bytes: &[u8]) {
+        let mut packet = [0; PACKET_SIZE];
+        packet[..bytes.len()].copy_from_slice(bytes);
+
+        write(0x00); write(0x30);
+        for bit in 0..PACKET_BITS {
+            write(if packet[bit / 8] & 1 << (bit % 8) != 0 { 0x10 } else { 0x20 });
+            write(0x30);
+        }
+        write(0x20); write(0x30);
+    }
+
+    #[test]
+    fn attr_set_picks_a_file_and_cancels_the_mask() {
+        let mut sgb = Sgb::new();
+
+        // ATTR_TRN with every cell of file 1 on palette 3
+        send_packet(|val| sgb.write_joypad(val), &[0x15 << 3 | 1]);
+        let mut data = vec![0; TRANSFER_SIZE];
+        data[ATTR_FILE_SIZE..ATTR_FILE_SIZE * 2].fill(0xFF);
+        sgb.transfer(&data);
+
+        send_packet(|val| sgb.write_joypad(val), &[0x17 << 3 | 1, 0x01]);
+        assert_eq!(sgb.mask, Mask::Freeze);
+
+        // Without bit 6 the mask stays
+        send_packet(|val| sgb.write_joypad(val), &[0x16 << 3 | 1, 0x01]);
+        assert_eq!(sgb.attributes, [[3; COLUMNS]; ROWS]);
+        assert_eq!(sgb.mask, Mask::Freeze);
+
+        send_packet(|val| sgb.write_joypad(val), &[0x16 << 3 | 1, 0x40]);
+        assert_eq!(sgb.attributes, [[0; COLUMNS]; ROWS]);
+        assert_eq!(sgb.mask, Mask::Off);
+    }
+
+    fn packet_with_colors(command: u8, colors: &[u16]) -> Vec<u8> {
+        let mut packet = vec![command << 3 | 1];
+        for color in colors { packet.extend_from_slice(&color.to_le_bytes()); }
+        packet
+    }
+
+    #[test]
+    fn decodes_palette_packets() {
+        let mut sgb = Sgb::new();
+        send_packet(|val| sgb.write_joypad(val), &packet_with_colors(0x03, &[0x7FFF, 0x001F, 0x03E0, 0x7C00, 0x1111, 0x2222, 0x3333]));
+
+        assert_eq!(sgb.palettes[1], [0x7FFF, 0x001F, 0x03E0, 0x7C00]);
+        assert_eq!(sgb.palettes[2], [0x7FFF, 0x1111, 0x2222, 0x3333]);
+        assert_eq!(sgb.palettes[0][0], 0x7FFF);
+        assert_eq!(sgb.palettes[3][0], 0x7FFF);
+    }
+
+    #[test]
+    fn drops_packets_without_a_stop_bit() {
+        let mut sgb = Sgb::new();
+        let packet = packet_with_colors(0x00, &[0x1234]);
+
+        sgb.write_joypad(0x00); sgb.write_joypad(0x30);
+        for bit in 0..PACKET_BITS {
+            sgb.write_joypad(if packet.get(bit / 8).is_some_and(|b| b & 1 << (bit % 8) != 0) { 0x10 } else { 0x20 });
+            sgb.write_joypad(0x30);
+        }
+        sgb.write_joypad(0x10); sgb.write_joypad(0x30);
+
+        assert_eq!(sgb.palettes[0][0], 0x7FFF);
+    }
+
+    #[test]
+    fn collects_multi_packet_commands() {
+        let mut sgb = Sgb::new();
+
+        // ATTR_BLK announced as two packets: one block, inside 1, border 2, outside 3
+        let mut command = vec![0x04 << 3 | 2, 0x01, 0x07, 0x39, 1, 1, 3, 3];
+        command.resize(PACKET_SIZE * 2, 0);
+
+        send_packet(|val| sgb.write_joypad(val), &command[..PACKET_SIZE]);
+        assert_eq!(sgb.attributes, [[0; COLUMNS]; ROWS]);
+
+        send_packet(|val| sgb.write_joypad(val), &command[PACKET_SIZE..]);
+        assert_eq!(sgb.attributes[0][0], 3);
+        assert_eq!(sgb.attributes[1][1], 2);
+        assert_eq!(sgb.attributes[2][2], 1);
+        assert_eq!(sgb.attributes[3][2], 2);
+        assert_eq!(sgb.attributes[4][4], 3);
+    }
+
+    #[test]
+    fn decodes_attribute_packets() {
+        let mut sgb = Sgb::new();
+
+        // ATTR_DIV: split at row 9, 1 above, 2 on the line, 3 below
+        send_packet(|val| sgb.write_joypad(val), &[0x06 << 3 | 1, 0x40 | 2 << 4 | 1 << 2 | 3, 9]);
+        assert_eq!([sgb.attributes[8][0], sgb.attributes[9][5], sgb.attributes[10][19]], [1, 2, 3]);
+
+        // ATTR_LIN: row 0 gets palette 2, column 19 palette 1
+        send_packet(|val| sgb.write_joypad(val), &[0x05 << 3 | 1, 2, 0x80 | 2 << 5, 1 << 5 | 19]);
+        assert_eq!([sgb.attributes[0][0], sgb.attributes[5][19], sgb.attributes[5][18]], [2, 1, 1]);
+
+        // ATTR_CHR: five cells downward from (4, 16), wrapping into the next column
+        send_packet(|val| sgb.write_joypad(val), &[0x07 << 3 | 1, 4, 16, 5, 0, 1, 0b00_01_10_11, 0b11_00_00_00]);
+        assert_eq!([sgb.attributes[16][4], sgb.attributes[17][4], sgb.attributes[0][5], sgb.attributes[1][5], sgb.attributes[2][5]], [0, 1, 2, 3, 3]);
+    }
+
+    #[test]
+    fn mlt_req_sets_the_player_count() {
+        let mut sgb = Sgb::new();
+        send_packet(|val| sgb.write_joypad(val), &[0x11 << 3 | 1, 0x03]);
+        assert_eq!(sgb.players(), 4);
+
+        send_packet(|val| sgb.write_joypad(val), &[0x11 << 3 | 1, 0x00]);
+        assert_eq!(sgb.players(), 1);
+    }
+}
@@ -18,26 +18,43 @@ impl Timer {
         }
     }
 
+    pub fn set_divider(&mut self, value: u16) {
+        self.div_counter = value;
+        self.write_byte(TimerPointers::Div, (value >> 8) as u8);
+    }
+
+    // An M-cycle at a time, so a long stall (STOP, HDMA) still sees every TIMA tick
     pub fn step(&mut self, cycles: u16) {
+        let mut left = cycles;
+        while left > 0 {
+            let slice = left.min(4);
+            self.tick(slice);
+            left -= slice;
+        }
+    }
+
+    fn tick(&mut self, cycles: u16) {
         use TimerPointers::*;
 
-        self.div_counter += self.div_counter.wrapping_add(cycles);
-
-        if self.read_byte(Tac) & 0x04 != 0 { 
-            if (self.div_counter as usize) % match self.read_byte(Tac) & 0x3 { 
-                0b00 => 0x400, 0b01 => 0x00F, 0b10 => 0x040, 0b11 => 0x100, _ => 0x0,
-            } == 0 {
-                let tima = self.read_byte(Tima);
-                if tima == 0xFF {
-                    self.write_byte(Tima, self.read_byte(Tma));
-                    self.request_interrupt();
-                } else {
-                    self.write_byte(Tima, tima.wrapping_add(1));
-                }
+        self.div_counter = self.div_counter.wrapping_add(cycles);
+
+        // TIMA ticks every 1024, 16, 64 or 256 cycles
+        let period: u16 = match self.read_byte(Tac) & 0x3 {
+            0b00 => 0x400, 0b01 => 0x010, 0b10 => 0x040, _ => 0x100,
+        };
+
+        if self.read_byte(Tac) & 0x04 != 0 && self.div_counter.is_multiple_of(period) {
+            let tima = self.read_byte(Tima);
+            if tima == 0xFF {
+                self.write_byte(Tima, self.read_byte(Tma));
+                self.request_interrupt();
+            } else {
+                self.write_byte(Tima, tima.wrapping_add(1));
             }
         }
 
-        self.write_byte(Div, self.div_counter as u8);
+        // DIV is the upper byte of the internal counter
+        self.write_byte(Div, (self.div_counter >> 8) as u8);
     }
 
     fn request_interrupt(&mut self) {
@@ -51,4 +68,23 @@ impl Timer {
     fn write_byte(&mut self, field: TimerPointers, to: u8) {
         self.memory.borrow_mut().write_byte(field as u16, to);
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::tests::bus;
+    use crate::model::Model;
+
+    #[test]
+    fn long_stalls_tick_tima_every_period() {
+        for (tac, period) in [(0x04, 1024), (0x05, 16), (0x06, 64), (0x07, 256)] {
+            let mut timer = Timer::new(Rc::new(RefCell::new(bus(Model::Dmg))));
+            timer.write_byte(TimerPointers::Tima, 0x00);
+            timer.write_byte(TimerPointers::Tac, tac);
+
+            timer.step(period * 8 + 2);
+            assert_eq!(timer.read_byte(TimerPointers::Tima), 8, "TAC {:02X}", tac);
+        }
+    }
+}
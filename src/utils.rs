@@ -0,0 +1,7 @@
+// CGB and SGB colors are RGB555; stretch each 5-bit channel to 8 bits for the frame buffer
+pub fn rgb555(color: u16) -> u32 {
+    let scale = |c: u32| (c << 3) | (c >> 2);
+    let (r, g, b) = (color as u32 & 0x1F, (color as u32 >> 5) & 0x1F, (color as u32 >> 10) & 0x1F);
+
+    scale(r) << 16 | scale(g) << 8 | scale(b)
+}